@@ -1,56 +1,459 @@
-pub fn solve<T: AsRef<str>>(lines: &[T]) -> (usize, usize) {
-    let commands: Vec<(String, i32)> = lines
-        .into_iter()
-        .map(|l| -> (String, i32) {
-            let (command, quantity) = l.as_ref().split_once(" ").unwrap();
-            (String::from(command), quantity.parse::<i32>().unwrap())
-        })
-        .collect();
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Forward(i64),
+    Down(i64),
+    Up(i64),
+}
 
-    (part_one(&commands) as usize, part_two(&commands) as usize)
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Malformed { line: usize, value: String },
+    UnknownCommand { line: usize, command: String },
 }
 
-fn part_one(lines: &Vec<(String, i32)>) -> i32 {
-    let (position, depth): (i32, i32) = lines.into_iter().fold(
-        (0, 0),
-        |(position, depth), (command, distance)| match command.as_str() {
-            "forward" => (position + distance, depth),
-            "down" => (position, depth + distance),
-            "up" => (position, depth - distance),
-            _ => {
-                println!("Unknown command: {:?}!", command);
-                (position, depth)
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed { line, value } => {
+                write!(f, "Not a command on line {}: {:?}!", line + 1, value)
             }
-        },
-    );
+            Self::UnknownCommand { line, command } => {
+                write!(f, "Unknown command on line {}: {:?}!", line + 1, command)
+            }
+        }
+    }
+}
+
+impl Command {
+    fn parse(line: usize, value: &str) -> Result<Self, ParseError> {
+        let malformed = || ParseError::Malformed {
+            line,
+            value: value.to_string(),
+        };
+        let (command, quantity) = value.split_once(' ').ok_or_else(malformed)?;
+        let quantity = quantity.parse::<i64>().map_err(|_| malformed())?;
 
-    position * depth
+        match command {
+            "forward" => Ok(Self::Forward(quantity)),
+            "down" => Ok(Self::Down(quantity)),
+            "up" => Ok(Self::Up(quantity)),
+            _ => Err(ParseError::UnknownCommand {
+                line,
+                command: command.to_string(),
+            }),
+        }
+    }
 }
 
-fn part_two(lines: &Vec<(String, i32)>) -> i32 {
-    let (position, depth, _): (i32, i32, _) =
-        lines
-            .into_iter()
-            .fold(
-                (0, 0, 0),
-                |(position, depth, aim), (command, distance)| match command.as_str() {
-                    "forward" => (position + distance, depth + (aim * distance), aim),
-                    "down" => (position, depth, aim + distance),
-                    "up" => (position, depth, aim - distance),
-                    _ => {
-                        println!("Unknown command: {:?}!", command);
-                        (position, depth, aim)
-                    }
-                },
-            );
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Forward(n) => write!(f, "forward {}", n),
+            Self::Down(n) => write!(f, "down {}", n),
+            Self::Up(n) => write!(f, "up {}", n),
+        }
+    }
+}
 
-    position * depth
+/// Which steering rules a course is interpreted with: part one moves depth
+/// directly, part two moves aim and dives on `forward`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    Simple,
+    Aimed,
+}
+
+impl Model {
+    fn steer_fn(&self) -> fn(&Submarine, &Command) -> Option<Submarine> {
+        match self {
+            Self::Simple => Submarine::steer,
+            Self::Aimed => Submarine::steer_with_aim,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlanError {
+    Unreachable,
+    TooLong { shortest: usize },
+}
+
+#[derive(Debug, PartialEq)]
+struct Overflow {
+    index: usize,
+    command: Command,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Overflow at command {} ({})!",
+            self.index + 1,
+            self.command
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Submarine {
+    position: i64,
+    depth: i64,
+    aim: i64,
+}
+
+impl Submarine {
+    fn pilot(
+        commands: &[Command],
+        steer: fn(&Self, &Command) -> Option<Self>,
+    ) -> Result<Self, Overflow> {
+        commands
+            .iter()
+            .enumerate()
+            .try_fold(Self::default(), |submarine, (index, command)| {
+                steer(&submarine, command).ok_or(Overflow {
+                    index,
+                    command: *command,
+                })
+            })
+    }
+
+    fn steer(&self, command: &Command) -> Option<Self> {
+        let mut next = *self;
+
+        match *command {
+            Command::Forward(n) => next.position = self.position.checked_add(n)?,
+            Command::Down(n) => next.depth = self.depth.checked_add(n)?,
+            Command::Up(n) => next.depth = self.depth.checked_sub(n)?,
+        }
+
+        Some(next)
+    }
+
+    fn steer_with_aim(&self, command: &Command) -> Option<Self> {
+        let mut next = *self;
+
+        match *command {
+            Command::Forward(n) => {
+                next.position = self.position.checked_add(n)?;
+                next.depth = self.depth.checked_add(self.aim.checked_mul(n)?)?;
+            }
+            Command::Down(n) => next.aim = self.aim.checked_add(n)?,
+            Command::Up(n) => next.aim = self.aim.checked_sub(n)?,
+        }
+
+        Some(next)
+    }
+
+    fn product(&self) -> i128 {
+        self.position as i128 * self.depth as i128
+    }
+}
+
+/// Builds a shortest course of positive-quantity commands that ends at
+/// `(position, depth)`, failing if it would need more than `max_commands`.
+pub fn plan(
+    position: i64,
+    depth: i64,
+    model: Model,
+    max_commands: Option<usize>,
+) -> Result<Vec<Command>, PlanError> {
+    let course = match model {
+        Model::Simple => plan_simple(position, depth)?,
+        Model::Aimed => plan_aimed(position, depth)?,
+    };
+
+    match max_commands {
+        Some(max) if course.len() > max => Err(PlanError::TooLong {
+            shortest: course.len(),
+        }),
+        _ => Ok(course),
+    }
+}
+
+fn plan_simple(position: i64, depth: i64) -> Result<Vec<Command>, PlanError> {
+    if position < 0 {
+        return Err(PlanError::Unreachable);
+    }
+
+    let mut course = vec![];
+    if position > 0 {
+        course.push(Command::Forward(position));
+    }
+    if let Some(dive) = dive(depth)? {
+        course.push(dive);
+    }
+
+    Ok(course)
+}
+
+fn plan_aimed(position: i64, depth: i64) -> Result<Vec<Command>, PlanError> {
+    if position < 0 || (position == 0 && depth != 0) {
+        return Err(PlanError::Unreachable);
+    }
+    if position == 0 {
+        return Ok(vec![]);
+    }
+    if depth == 0 {
+        return Ok(vec![Command::Forward(position)]);
+    }
+    if depth % position == 0 {
+        return Ok(vec![
+            dive(depth / position)?.unwrap(),
+            Command::Forward(position),
+        ]);
+    }
+
+    // Anything else takes three commands: cruise level, then pick up the
+    // whole depth on a final short leg. Any divisor of `depth` shorter than
+    // `position` works; sharing one with `position - 1` is cheap to find and
+    // often keeps the aim change small.
+    let leg = gcd(depth.unsigned_abs(), position as u64 - 1) as i64;
+
+    Ok(vec![
+        Command::Forward(position - leg),
+        dive(depth / leg)?.unwrap(),
+        Command::Forward(leg),
+    ])
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The command that changes depth (or aim) by `delta`, if any. An `up` of
+/// `i64::MIN` can't be written with a positive quantity.
+fn dive(delta: i64) -> Result<Option<Command>, PlanError> {
+    match delta {
+        0 => Ok(None),
+        d if d > 0 => Ok(Some(Command::Down(d))),
+        d => d
+            .checked_neg()
+            .map(|up| Some(Command::Up(up)))
+            .ok_or(PlanError::Unreachable),
+    }
+}
+
+/// Replays `commands` through the simulator and checks they finish at
+/// `(position, depth)`.
+pub fn verify(commands: &[Command], position: i64, depth: i64, model: Model) -> bool {
+    match Submarine::pilot(commands, model.steer_fn()) {
+        Ok(submarine) => submarine.position == position && submarine.depth == depth,
+        Err(_) => false,
+    }
+}
+
+pub fn solve<T: AsRef<str>>(lines: &[T]) -> (i128, i128) {
+    // Every line must parse, so an overflow's command index is also its
+    // line number.
+    let commands: Vec<Command> = lines
+        .iter()
+        .enumerate()
+        .map(|(line, l)| Command::parse(line, l.as_ref()))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    (part_one(&commands), part_two(&commands))
+}
+
+fn part_one(commands: &[Command]) -> i128 {
+    Submarine::pilot(commands, Model::Simple.steer_fn())
+        .unwrap_or_else(|overflow| panic!("{}", overflow))
+        .product()
+}
+
+fn part_two(commands: &[Command]) -> i128 {
+    Submarine::pilot(commands, Model::Aimed.steer_fn())
+        .unwrap_or_else(|overflow| panic!("{}", overflow))
+        .product()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn command_parse() {
+        assert_eq!(Command::parse(0, "forward 5"), Ok(Command::Forward(5)));
+        assert_eq!(Command::parse(0, "down 3"), Ok(Command::Down(3)));
+        assert_eq!(Command::parse(0, "up 8"), Ok(Command::Up(8)));
+        assert_eq!(
+            Command::parse(1, "backward 2"),
+            Err(ParseError::UnknownCommand {
+                line: 1,
+                command: String::from("backward")
+            })
+        );
+        assert_eq!(
+            Command::parse(2, "forward"),
+            Err(ParseError::Malformed {
+                line: 2,
+                value: String::from("forward")
+            })
+        );
+        assert_eq!(
+            Command::parse(3, "down five").unwrap_err().to_string(),
+            "Not a command on line 4: \"down five\"!"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown command on line 1: \"sideways\"!")]
+    fn solve_rejects_unknown_commands() {
+        solve(&["sideways 4", "forward 2"]);
+    }
+
+    #[test]
+    fn submarine_negative_depth() {
+        let commands = [Command::Forward(3), Command::Up(5)];
+
+        assert_eq!(part_one(&commands), -15);
+    }
+
+    #[test]
+    fn submarine_wide_product() {
+        let commands = [Command::Forward(i64::MAX), Command::Down(i64::MAX)];
+
+        assert_eq!(part_one(&commands), i64::MAX as i128 * i64::MAX as i128);
+    }
+
+    #[test]
+    fn submarine_overflow() {
+        let commands = [
+            Command::Down(i64::MAX / 2),
+            Command::Forward(2),
+            Command::Forward(3),
+        ];
+
+        let result = Submarine::pilot(&commands, Model::Aimed.steer_fn());
+
+        assert_eq!(
+            result,
+            Err(Overflow {
+                index: 2,
+                command: Command::Forward(3),
+            })
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Overflow at command 3 (forward 3)!"
+        );
+    }
+
+    #[test]
+    fn plan_simple_course() {
+        let course = plan(15, -10, Model::Simple, None).unwrap();
+
+        assert_eq!(course, vec![Command::Forward(15), Command::Up(10)]);
+        assert!(verify(&course, 15, -10, Model::Simple));
+    }
+
+    #[test]
+    fn plan_aimed_course() {
+        assert_eq!(plan(0, 0, Model::Aimed, None), Ok(vec![]));
+        assert_eq!(
+            plan(15, 60, Model::Aimed, None),
+            Ok(vec![Command::Down(4), Command::Forward(15)])
+        );
+        assert_eq!(
+            plan(15, 14, Model::Aimed, None),
+            Ok(vec![
+                Command::Forward(1),
+                Command::Down(1),
+                Command::Forward(14),
+            ])
+        );
+
+        for position in 0..12 {
+            for depth in -40..40 {
+                if let Ok(course) = plan(position, depth, Model::Aimed, None) {
+                    assert!(course.len() <= 3);
+                    assert!(verify(&course, position, depth, Model::Aimed));
+                } else {
+                    assert_eq!(position, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plan_aimed_large_target() {
+        let course = plan(1_000_000_000_000, 7, Model::Aimed, None).unwrap();
+
+        assert_eq!(
+            course,
+            vec![
+                Command::Forward(999_999_999_993),
+                Command::Down(1),
+                Command::Forward(7),
+            ]
+        );
+        assert!(verify(&course, 1_000_000_000_000, 7, Model::Aimed));
+
+        let course = plan(1_000_000_000_000, 999_999_999_989 * 2, Model::Aimed, None).unwrap();
+        assert_eq!(course[1], Command::Down(999_999_999_989 * 2));
+        assert!(verify(
+            &course,
+            1_000_000_000_000,
+            999_999_999_989 * 2,
+            Model::Aimed
+        ));
+
+        let course = plan(i64::MAX, i64::MAX - 1, Model::Aimed, None).unwrap();
+        assert_eq!(
+            course,
+            vec![
+                Command::Forward(1),
+                Command::Down(1),
+                Command::Forward(i64::MAX - 1),
+            ]
+        );
+        assert!(verify(&course, i64::MAX, i64::MAX - 1, Model::Aimed));
+    }
+
+    #[test]
+    fn plan_errors() {
+        assert_eq!(
+            plan(-1, 0, Model::Simple, None),
+            Err(PlanError::Unreachable)
+        );
+        assert_eq!(plan(0, 5, Model::Aimed, None), Err(PlanError::Unreachable));
+        assert_eq!(
+            plan(15, 14, Model::Aimed, Some(2)),
+            Err(PlanError::TooLong { shortest: 3 })
+        );
+        assert_eq!(
+            plan(1, i64::MIN, Model::Simple, None),
+            Err(PlanError::Unreachable)
+        );
+        assert_eq!(
+            plan(1, i64::MIN, Model::Aimed, None),
+            Err(PlanError::Unreachable)
+        );
+    }
+
+    #[test]
+    fn verify_example_course() {
+        let course = [
+            Command::Forward(5),
+            Command::Down(5),
+            Command::Forward(8),
+            Command::Up(3),
+            Command::Down(8),
+            Command::Forward(2),
+        ];
+
+        assert!(verify(&course, 15, 10, Model::Simple));
+        assert!(verify(&course, 15, 60, Model::Aimed));
+        assert!(!verify(&course, 15, 60, Model::Simple));
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = [
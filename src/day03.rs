@@ -1,114 +1,464 @@
-pub fn solve<T: AsRef<str>>(lines: &[T]) -> (usize, usize) {
-    (part_one(&lines) as usize, part_two(&lines) as usize)
-}
+use std::fmt;
 
-fn part_one<T: AsRef<str>>(lines: &[T]) -> i32 {
-    let aggregates = parse_aggregates(lines);
-    let mcd: Vec<u8> = most_common_digits(&aggregates);
-    let lcd: Vec<u8> = mcd.iter().map(|i| *i ^ 1).collect();
+const WORD_BITS: usize = u64::BITS as usize;
 
-    let gamma = binary_vec_to_i32(&mcd);
-    let epsilon = binary_vec_to_i32(&lcd);
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticError {
+    Empty,
+    NoCriteria,
+    InconsistentWidth { line: usize, width: usize },
+    InvalidDigit { line: usize, column: usize },
+    NoReadingsLeft { column: usize },
+    TooWide { width: usize },
+}
 
-    gamma * epsilon
+impl fmt::Display for DiagnosticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Report has no readings!"),
+            Self::NoCriteria => write!(f, "No bit criteria given!"),
+            Self::InconsistentWidth { line, width } => {
+                write!(f, "Reading {} has a different width ({})!", line + 1, width)
+            }
+            Self::InvalidDigit { line, column } => write!(
+                f,
+                "Reading {} has a non-binary digit in column {}!",
+                line + 1,
+                column + 1
+            ),
+            Self::NoReadingsLeft { column } => {
+                write!(f, "No readings left after column {}!", column + 1)
+            }
+            Self::TooWide { width } => {
+                write!(f, "Readings of width {} are too wide for a number!", width)
+            }
+        }
+    }
 }
 
-enum LifeSupportRating {
-    OxygenGeneratorRating,
-    CO2ScrubberRating,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Digit {
+    Zero,
+    One,
 }
 
-fn part_two<T: AsRef<str>>(lines: &&[T]) -> i32 {
-    get_life_support_rating(lines, LifeSupportRating::OxygenGeneratorRating)
-        * get_life_support_rating(lines, LifeSupportRating::CO2ScrubberRating)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preference {
+    MostCommon,
+    LeastCommon,
 }
 
-fn get_life_support_rating<T: AsRef<str>>(lines: &&[T], mapper_fn: LifeSupportRating) -> i32 {
-    let mut i = 0;
-    let mut keep: Vec<&str> = lines.iter().map(|l| l.as_ref()).collect();
+/// How to pick which digit survives in a column: keep the most or least
+/// common one, and which digit wins when both are equally common.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitCriteria {
+    pub preference: Preference,
+    pub tie: Digit,
+}
 
-    let result = loop {
-        let aggregates = parse_aggregates(&keep);
-        let mapper: Vec<u8> = match mapper_fn {
-            LifeSupportRating::OxygenGeneratorRating => most_common_digits(&aggregates),
-            LifeSupportRating::CO2ScrubberRating => least_common_digits(&aggregates),
-        };
-        let m = mapper[i];
+impl BitCriteria {
+    pub const OXYGEN_GENERATOR: Self = Self {
+        preference: Preference::MostCommon,
+        tie: Digit::One,
+    };
+    pub const CO2_SCRUBBER: Self = Self {
+        preference: Preference::LeastCommon,
+        tie: Digit::Zero,
+    };
 
-        keep = keep
-            .into_iter()
-            .map(|l| l.as_ref())
-            .filter(|l: &&str| {
-                l.chars().nth(i).unwrap() == std::char::from_digit(m.into(), 10).unwrap()
-            })
-            .collect();
+    fn select(&self, zeros: usize, ones: usize) -> usize {
+        if zeros == ones {
+            return self.tie as usize;
+        }
 
-        if keep.len() == 1 {
-            break keep.into_iter().nth(0).unwrap();
-        } else {
-            i += 1;
+        let most_common = (ones > zeros) as usize;
+        match self.preference {
+            Preference::MostCommon => most_common,
+            Preference::LeastCommon => most_common ^ 1,
         }
-    };
+    }
+}
 
-    i32::from_str_radix(result, 2).unwrap()
+/// A diagnostic report with every reading packed into `words` consecutive
+/// `u64`s, most significant word first, so column 0 is the highest bit.
+#[derive(Debug, PartialEq)]
+struct Report {
+    width: usize,
+    words: usize,
+    bits: Vec<u64>,
 }
 
-fn parse_aggregates<T: AsRef<str>>(lines: &[T]) -> Vec<Vec<u32>> {
-    let iter = lines.into_iter();
-    let length = lines[0].as_ref().len();
+impl Report {
+    fn parse<T: AsRef<str>>(lines: &[T]) -> Result<Self, DiagnosticError> {
+        let width = lines.first().map_or(0, |line| line.as_ref().len());
+        if width == 0 {
+            return Err(DiagnosticError::Empty);
+        }
 
-    let mut aggregates: Vec<Vec<u32>> = vec![Vec::new(); length];
-    iter.for_each(|line| {
-        line.as_ref().chars().enumerate().for_each(|(i, digit)| {
-            let value = digit.to_string().parse::<u32>().unwrap();
+        let words = width.div_ceil(WORD_BITS);
+        let mut bits = vec![0; lines.len() * words];
 
-            aggregates.get_mut(i).unwrap().push(value);
-        });
-    });
+        for (index, (reading, line)) in bits.chunks_exact_mut(words).zip(lines).enumerate() {
+            let line = line.as_ref();
+            if line.len() != width {
+                return Err(DiagnosticError::InconsistentWidth {
+                    line: index,
+                    width: line.len(),
+                });
+            }
 
-    aggregates
-}
+            for (column, digit) in line.bytes().enumerate() {
+                match digit {
+                    b'0' => {}
+                    b'1' => {
+                        let (word, mask) = Self::locate(width, words, column);
+                        reading[word] |= mask;
+                    }
+                    _ => {
+                        return Err(DiagnosticError::InvalidDigit {
+                            line: index,
+                            column,
+                        })
+                    }
+                }
+            }
+        }
 
-fn most_common_digits(aggregates: &Vec<Vec<u32>>) -> Vec<u8> {
-    aggregates
-        .clone()
-        .into_iter()
-        .map(|digits| {
-            let sum: u32 = digits.clone().into_iter().sum();
+        Ok(Self { width, words, bits })
+    }
 
-            if (sum as f64) < (digits.len() as f64 / 2.0).ceil() {
-                0
-            } else {
-                1
+    fn locate(width: usize, words: usize, column: usize) -> (usize, u64) {
+        let position = width - 1 - column;
+
+        (
+            words - 1 - position / WORD_BITS,
+            1 << (position % WORD_BITS),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.bits.len() / self.words
+    }
+
+    fn reading(&self, index: usize) -> &[u64] {
+        &self.bits[index * self.words..(index + 1) * self.words]
+    }
+
+    fn column_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.width];
+
+        for reading in self.bits.chunks_exact(self.words) {
+            for (w, &word) in reading.iter().enumerate() {
+                let base = (self.words - 1 - w) * WORD_BITS;
+                let mut rest = word;
+
+                while rest != 0 {
+                    let position = base + rest.trailing_zeros() as usize;
+                    counts[self.width - 1 - position] += 1;
+                    rest &= rest - 1;
+                }
+            }
+        }
+
+        counts
+    }
+
+    fn width_mask(&self) -> Vec<u64> {
+        let mut mask = vec![u64::MAX; self.words];
+        let spare = self.words * WORD_BITS - self.width;
+        mask[0] = u64::MAX >> spare;
+
+        mask
+    }
+
+    fn gamma(&self) -> Vec<u64> {
+        let total = self.len();
+        let mut gamma = vec![0; self.words];
+
+        for (column, ones) in self.column_counts().into_iter().enumerate() {
+            if 2 * ones >= total {
+                let (word, mask) = Self::locate(self.width, self.words, column);
+                gamma[word] |= mask;
             }
-        })
-        .collect()
+        }
+
+        gamma
+    }
+
+    fn epsilon(&self) -> Vec<u64> {
+        self.gamma()
+            .into_iter()
+            .zip(self.width_mask())
+            .map(|(g, mask)| !g & mask)
+            .collect()
+    }
 }
 
-fn least_common_digits(aggregates: &Vec<Vec<u32>>) -> Vec<u8> {
-    aggregates
-        .clone()
-        .into_iter()
-        .map(|digits| {
-            let sum: u32 = digits.clone().into_iter().sum();
+#[derive(Debug, Default, PartialEq)]
+struct Node {
+    count: usize,
+    children: [Option<usize>; 2],
+}
+
+/// A binary trie over a report's readings, one level per column, with each
+/// node counting the readings that share its prefix.
+#[derive(Debug, PartialEq)]
+pub struct Trie {
+    width: usize,
+    words: usize,
+    nodes: Vec<Node>,
+}
+
+impl Trie {
+    pub fn parse<T: AsRef<str>>(lines: &[T]) -> Result<Self, DiagnosticError> {
+        Ok(Self::build(&Report::parse(lines)?))
+    }
+
+    fn build(report: &Report) -> Self {
+        let mut nodes = vec![Node::default()];
 
-            if (sum as f64) < (digits.len() as f64 / 2.0).ceil() {
-                1
+        for index in 0..report.len() {
+            let reading = report.reading(index);
+            let mut current = 0;
+            nodes[current].count += 1;
+
+            for column in 0..report.width {
+                let (word, mask) = Report::locate(report.width, report.words, column);
+                let digit = (reading[word] & mask != 0) as usize;
+
+                current = match nodes[current].children[digit] {
+                    Some(child) => child,
+                    None => {
+                        nodes.push(Node::default());
+                        nodes[current].children[digit] = Some(nodes.len() - 1);
+                        nodes.len() - 1
+                    }
+                };
+                nodes[current].count += 1;
+            }
+        }
+
+        Self {
+            width: report.width,
+            words: report.words,
+            nodes,
+        }
+    }
+
+    /// How many readings start with `prefix`, given as a string of binary
+    /// digits.
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        prefix
+            .bytes()
+            .try_fold(0, |current, digit| {
+                let digit = match digit {
+                    b'0' => 0,
+                    b'1' => 1,
+                    _ => return None,
+                };
+
+                self.nodes[current].children[digit]
+            })
+            .map_or(0, |node| self.nodes[node].count)
+    }
+
+    fn count(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |n| self.nodes[n].count)
+    }
+
+    /// Walks down the trie choosing a digit per column with `criteria[column]`,
+    /// reusing the last entry for any remaining columns, until one reading is
+    /// left. Returns that reading packed like the report.
+    pub fn rating(&self, criteria: &[BitCriteria]) -> Result<Vec<u64>, DiagnosticError> {
+        let last = criteria.last().ok_or(DiagnosticError::NoCriteria)?;
+        let mut words = vec![0; self.words];
+        let mut current = 0;
+
+        for column in 0..self.width {
+            let node = &self.nodes[current];
+            let [zero, one] = node.children;
+            let digit = if node.count == 1 {
+                one.is_some() as usize
             } else {
-                0
+                let rule = criteria.get(column).unwrap_or(last);
+                rule.select(self.count(zero), self.count(one))
+            };
+
+            if digit == 1 {
+                let (word, mask) = Report::locate(self.width, self.words, column);
+                words[word] |= mask;
             }
-        })
-        .collect()
+            current = node.children[digit].ok_or(DiagnosticError::NoReadingsLeft { column })?;
+        }
+
+        Ok(words)
+    }
+
+    /// Like `rating`, but as a number when readings are at most 64 digits.
+    pub fn rating_value(&self, criteria: &[BitCriteria]) -> Result<u64, DiagnosticError> {
+        to_u64(&self.rating(criteria)?, self.width)
+    }
 }
 
-fn binary_vec_to_i32(binary: &Vec<u8>) -> i32 {
-    binary.iter().fold(0, |acc, &b| acc * 2 + b as i32)
+/// The power consumption and life support rating. Both are products of two
+/// readings of up to 64 digits, so they're returned as `u128`.
+pub fn solve<T: AsRef<str>>(lines: &[T]) -> (u128, u128) {
+    let report = Report::parse(lines).unwrap_or_else(|e| panic!("{}", e));
+    let trie = Trie::build(&report);
+
+    (
+        part_one(&report).unwrap_or_else(|e| panic!("{}", e)),
+        part_two(&trie).unwrap_or_else(|e| panic!("{}", e)),
+    )
+}
+
+fn part_one(report: &Report) -> Result<u128, DiagnosticError> {
+    let gamma = to_u64(&report.gamma(), report.width)?;
+    let epsilon = to_u64(&report.epsilon(), report.width)?;
+
+    Ok(gamma as u128 * epsilon as u128)
+}
+
+fn part_two(trie: &Trie) -> Result<u128, DiagnosticError> {
+    let oxygen_generator = trie.rating_value(&[BitCriteria::OXYGEN_GENERATOR])?;
+    let co2_scrubber = trie.rating_value(&[BitCriteria::CO2_SCRUBBER])?;
+
+    Ok(oxygen_generator as u128 * co2_scrubber as u128)
+}
+
+fn to_u64(words: &[u64], width: usize) -> Result<u64, DiagnosticError> {
+    match words {
+        [word] => Ok(*word),
+        _ => Err(DiagnosticError::TooWide { width }),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn report_parse() {
+        let report = Report::parse(&["00100", "11110"]).unwrap();
+
+        assert_eq!(
+            report,
+            Report {
+                width: 5,
+                words: 1,
+                bits: vec![0b00100, 0b11110],
+            }
+        );
+    }
+
+    #[test]
+    fn report_wide_readings() {
+        let line = format!("1{}01", "0".repeat(68));
+        let report = Report::parse(&[line.as_str(), line.as_str(), &"1".repeat(71)]).unwrap();
+
+        assert_eq!(report.words, 2);
+        assert_eq!(report.reading(0), &[1 << 6, 1]);
+        assert_eq!(report.width_mask(), vec![0x7f, u64::MAX]);
+        assert_eq!(report.column_counts()[0], 3);
+        assert_eq!(report.column_counts()[1], 1);
+        assert_eq!(report.column_counts()[70], 3);
+        assert_eq!(report.gamma(), vec![1 << 6, 1]);
+        assert_eq!(report.epsilon(), vec![0x3f, u64::MAX - 1]);
+    }
+
+    #[test]
+    fn report_column_counts() {
+        let report = Report::parse(&["00100", "11110", "10110"]).unwrap();
+
+        assert_eq!(report.column_counts(), vec![2, 1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn trie_count_prefix() {
+        let trie = Trie::parse(&["00100", "11110", "10110", "10111"]).unwrap();
+
+        assert_eq!(trie.count_prefix(""), 4);
+        assert_eq!(trie.count_prefix("1"), 3);
+        assert_eq!(trie.count_prefix("1011"), 2);
+        assert_eq!(trie.count_prefix("10111"), 1);
+        assert_eq!(trie.count_prefix("01"), 0);
+        assert_eq!(trie.count_prefix("1x"), 0);
+    }
+
+    #[test]
+    fn trie_rating() {
+        let trie = Trie::parse(&["00100", "11110", "10110", "10111", "10101", "01111"]).unwrap();
+
+        assert_eq!(
+            trie.rating(&[BitCriteria::OXYGEN_GENERATOR]),
+            Ok(vec![0b10111])
+        );
+        assert_eq!(trie.rating(&[BitCriteria::CO2_SCRUBBER]), Ok(vec![0b00100]));
+    }
+
+    #[test]
+    fn trie_custom_rating() {
+        let trie = Trie::parse(&["00100", "11110", "10110", "10111", "10101", "01111"]).unwrap();
+        let majority_ties_to_zero = BitCriteria {
+            preference: Preference::MostCommon,
+            tie: Digit::Zero,
+        };
+
+        assert_eq!(trie.rating_value(&[majority_ties_to_zero]), Ok(0b10110));
+        assert_eq!(
+            trie.rating_value(&[BitCriteria::CO2_SCRUBBER, BitCriteria::OXYGEN_GENERATOR]),
+            Ok(0b01111)
+        );
+    }
+
+    #[test]
+    fn trie_rating_emptied() {
+        let trie = Trie::parse(&["101", "101", "111"]).unwrap();
+
+        assert_eq!(
+            trie.rating(&[BitCriteria::CO2_SCRUBBER]),
+            Err(DiagnosticError::NoReadingsLeft { column: 0 })
+        );
+    }
+
+    #[test]
+    fn report_errors() {
+        let empty: [&str; 0] = [];
+
+        assert_eq!(Report::parse(&empty), Err(DiagnosticError::Empty));
+        assert_eq!(
+            Report::parse(&["101", "11"]),
+            Err(DiagnosticError::InconsistentWidth { line: 1, width: 2 })
+        );
+        assert_eq!(
+            Report::parse(&["101", "121"]),
+            Err(DiagnosticError::InvalidDigit { line: 1, column: 1 })
+        );
+        assert_eq!(
+            Trie::parse(&["1".repeat(65)])
+                .unwrap()
+                .rating_value(&[BitCriteria::OXYGEN_GENERATOR]),
+            Err(DiagnosticError::TooWide { width: 65 })
+        );
+        assert_eq!(
+            Trie::parse(&["101", "011"]).unwrap().rating(&[]),
+            Err(DiagnosticError::NoCriteria)
+        );
+    }
+
+    #[test]
+    fn solve_wide_readings() {
+        let solution = solve(&["10".repeat(20)]);
+
+        assert_eq!(
+            solution,
+            (
+                268_650_182_136_095_589_211_250,
+                537_300_364_272_191_178_422_500
+            )
+        );
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = [
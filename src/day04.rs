@@ -1,5 +1,92 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
 use regex::Regex;
 
+mod server;
+
+pub use server::serve;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    MissingNumbers,
+    InvalidNumber { line: usize, value: String },
+    RaggedRow { line: usize },
+    DuplicateNumber { line: usize, number: i32 },
+}
+
+impl ParseError {
+    fn offset(self, by: usize) -> Self {
+        match self {
+            Self::MissingNumbers => Self::MissingNumbers,
+            Self::InvalidNumber { line, value } => Self::InvalidNumber {
+                line: line + by,
+                value,
+            },
+            Self::RaggedRow { line } => Self::RaggedRow { line: line + by },
+            Self::DuplicateNumber { line, number } => Self::DuplicateNumber {
+                line: line + by,
+                number,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingNumbers => write!(f, "No numbers to draw!"),
+            Self::InvalidNumber { line, value } => {
+                write!(f, "Not a number on line {}: {:?}!", line + 1, value)
+            }
+            Self::RaggedRow { line } => {
+                write!(f, "Row on line {} has the wrong length!", line + 1)
+            }
+            Self::DuplicateNumber { line, number } => {
+                write!(f, "Number {} repeated on line {}!", number, line + 1)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RuleError {
+    EmptyPattern,
+    PatternMismatch {
+        card: usize,
+        height: usize,
+        width: usize,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::EmptyPattern => write!(f, "Pattern has no squares to mark!"),
+            Self::PatternMismatch {
+                card,
+                height,
+                width,
+            } => write!(
+                f,
+                "Pattern doesn't fit card {}, which is {}x{}!",
+                card + 1,
+                height,
+                width
+            ),
+        }
+    }
+}
+
+fn parse_number(line: usize, value: &str) -> Result<i32, ParseError> {
+    value.parse::<i32>().map_err(|_| ParseError::InvalidNumber {
+        line,
+        value: value.to_string(),
+    })
+}
+
 #[derive(Clone, Debug, PartialEq)]
 enum Status {
     Marked,
@@ -14,10 +101,7 @@ struct Square {
 
 impl Square {
     fn new(value: i32) -> Self {
-        Self {
-            value,
-            status: Status::Clear,
-        }
+        Self::build(value, Status::Clear)
     }
 
     fn build(value: i32, status: Status) -> Self {
@@ -27,70 +111,212 @@ impl Square {
 
 type Row = Vec<Square>;
 
+/// A way to complete a card. Every rule expands to one or more patterns of
+/// squares for a given card, and the card wins once any pattern is fully
+/// marked.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WinRule {
+    Rows,
+    Columns,
+    Diagonals,
+    FourCorners,
+    Blackout,
+    Pattern(Vec<Vec<bool>>),
+}
+
+const STANDARD_RULES: &[WinRule] = &[WinRule::Rows, WinRule::Columns];
+
+impl WinRule {
+    /// Reads a custom pattern drawn with `#` for squares that must be marked,
+    /// e.g. `["#...#", ".#.#.", "..#..", ".#.#.", "#...#"]`.
+    pub fn parse_pattern<T: AsRef<str>>(lines: &[T]) -> Self {
+        Self::Pattern(
+            lines
+                .iter()
+                .map(|line| line.as_ref().chars().map(|c| c == '#').collect())
+                .collect(),
+        )
+    }
+
+    fn patterns(&self, height: usize, width: usize) -> Vec<Vec<(usize, usize)>> {
+        match self {
+            Self::Rows => (0..height)
+                .map(|r| (0..width).map(|c| (r, c)).collect())
+                .collect(),
+            Self::Columns => (0..width)
+                .map(|c| (0..height).map(|r| (r, c)).collect())
+                .collect(),
+            Self::Diagonals if height == width => vec![
+                (0..height).map(|i| (i, i)).collect(),
+                (0..height).map(|i| (i, width - 1 - i)).collect(),
+            ],
+            Self::Diagonals => vec![],
+            Self::FourCorners => {
+                let mut corners = vec![
+                    (0, 0),
+                    (0, width - 1),
+                    (height - 1, 0),
+                    (height - 1, width - 1),
+                ];
+                corners.sort();
+                corners.dedup();
+
+                vec![corners]
+            }
+            Self::Blackout => vec![(0..height)
+                .flat_map(|r| (0..width).map(move |c| (r, c)))
+                .collect()],
+            Self::Pattern(mask) => {
+                let fits = mask.len() == height && mask.iter().all(|row| row.len() == width);
+                let cells: Vec<_> = mask
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(r, row)| {
+                        row.iter()
+                            .enumerate()
+                            .filter(|(_, &set)| set)
+                            .map(move |(c, _)| (r, c))
+                    })
+                    .collect();
+
+                if fits && !cells.is_empty() {
+                    vec![cells]
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+}
+
+/// A bingo card that remembers where each number sits and how many squares
+/// of every winning pattern are marked, so a draw never rescans the board.
 #[derive(Clone, Debug, PartialEq)]
 struct Card {
     rows: Vec<Row>,
+    positions: HashMap<i32, (usize, usize)>,
+    square_patterns: Vec<Vec<Vec<usize>>>,
+    pattern_sizes: Vec<usize>,
+    pattern_marks: Vec<usize>,
     winning_number: Option<i32>,
 }
 
 impl Card {
     fn new(rows: Vec<Row>) -> Self {
-        Self {
+        let positions = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(move |(c, square)| (square.value, (r, c)))
+            })
+            .collect();
+
+        let mut card = Self {
             rows,
+            positions,
+            square_patterns: vec![],
+            pattern_sizes: vec![],
+            pattern_marks: vec![],
             winning_number: None,
+        };
+        card.apply_rules(STANDARD_RULES);
+
+        card
+    }
+
+    fn apply_rules(&mut self, rules: &[WinRule]) {
+        let height = self.rows.len();
+        let width = self.rows.first().map_or(0, |row| row.len());
+
+        self.square_patterns = vec![vec![vec![]; width]; height];
+        self.pattern_sizes = vec![];
+        self.pattern_marks = vec![];
+
+        for pattern in rules.iter().flat_map(|rule| rule.patterns(height, width)) {
+            let id = self.pattern_sizes.len();
+            let marked = pattern
+                .iter()
+                .filter(|&&(r, c)| self.rows[r][c].status == Status::Marked)
+                .count();
+
+            for &(r, c) in &pattern {
+                self.square_patterns[r][c].push(id);
+            }
+            self.pattern_sizes.push(pattern.len());
+            self.pattern_marks.push(marked);
         }
     }
 
-    fn build(lines: Vec<&str>) -> Self {
+    /// Reads one card from its rows of whitespace-separated numbers. `line`
+    /// in any error is the row within the card.
+    fn build(lines: Vec<&str>) -> Result<Self, ParseError> {
         let separator = Regex::new(r"\s+").expect("Invalid regex");
+        let mut seen = HashMap::new();
 
         let rows = lines
             .into_iter()
-            .map(|line| {
+            .enumerate()
+            .map(|(line, text)| {
                 separator
-                    .split(line)
-                    .filter(|&s| s != "")
-                    .map(|number| Square::new(number.parse::<i32>().unwrap()))
+                    .split(text.trim())
+                    .filter(|&s| !s.is_empty())
+                    .map(|value| {
+                        let number = parse_number(line, value)?;
+                        match seen.insert(number, line) {
+                            Some(_) => Err(ParseError::DuplicateNumber { line, number }),
+                            None => Ok(Square::new(number)),
+                        }
+                    })
                     .collect()
             })
-            .collect();
+            .collect::<Result<Vec<Row>, _>>()?;
 
-        Self {
-            rows,
-            winning_number: None,
+        if let Some(line) = rows.iter().position(|row| row.len() != rows[0].len()) {
+            return Err(ParseError::RaggedRow { line });
         }
+
+        Ok(Self::new(rows))
     }
 
     fn mark(&mut self, number: i32) {
-        for row in &mut self.rows {
-            for mut square in row {
-                if square.value == number {
-                    square.status = Status::Marked;
-                }
-            }
+        let Some(&(r, c)) = self.positions.get(&number) else {
+            return;
+        };
+        let square = &mut self.rows[r][c];
+        if square.status == Status::Marked {
+            return;
         }
-        if self.is_winner() {
+
+        square.status = Status::Marked;
+
+        let mut completes_pattern = false;
+        for &id in &self.square_patterns[r][c] {
+            self.pattern_marks[id] += 1;
+            completes_pattern |= self.pattern_marks[id] == self.pattern_sizes[id];
+        }
+
+        if completes_pattern && self.winning_number.is_none() {
             self.winning_number = Some(number);
         }
     }
 
-    fn cols(&self) -> Vec<Vec<Square>> {
-        (0..self.rows[0].len())
-            .map(|i| self.rows.iter().map(|row| row[i].clone()).collect())
-            .collect()
+    fn is_winner(&self) -> bool {
+        self.winning_number.is_some()
     }
 
-    fn is_winner(&self) -> bool {
-        let cols = self.cols();
-        let winning_row: Option<&Vec<Square>> = self
-            .rows
-            .iter()
-            .find(|row| row.iter().all(|square| square.status == Status::Marked));
-        let winning_col: Option<&Vec<Square>> = cols
-            .iter()
-            .find(|col| col.iter().all(|square| square.status == Status::Marked));
+    fn pattern_numbers(&self) -> Vec<Vec<i32>> {
+        let mut patterns = vec![vec![]; self.pattern_sizes.len()];
+        for (r, row) in self.square_patterns.iter().enumerate() {
+            for (c, ids) in row.iter().enumerate() {
+                for &id in ids {
+                    patterns[id].push(self.rows[r][c].value);
+                }
+            }
+        }
 
-        winning_row.is_some() || winning_col.is_some()
+        patterns
     }
 
     fn unmarked(&self) -> Vec<i32> {
@@ -105,116 +331,506 @@ impl Card {
     }
 }
 
-#[derive(Debug)]
-struct Winner {
-    score: i32,
+#[derive(Clone, Debug, PartialEq)]
+pub struct Winner {
+    pub card: usize,
+    pub draw: usize,
+    pub score: i32,
 }
 
 impl Winner {
-    fn new() -> Self {
-        Self { score: 0 }
-    }
-
-    fn calculate_score(card: &Card) -> Self {
-        let unmarked_total: i32 = card.unmarked().iter().sum();
+    fn calculate_score(card: usize, draw: usize, winning_card: &Card) -> Self {
+        let unmarked_total: i32 = winning_card.unmarked().iter().sum();
 
         Self {
-            score: unmarked_total * card.winning_number.unwrap(),
+            card,
+            draw,
+            score: unmarked_total * winning_card.winning_number.unwrap(),
         }
     }
 }
 
-struct Game {
+/// Everything that happened on one draw: which cards still in play had the
+/// number, and which of them won because of it.
+#[derive(Debug, PartialEq)]
+pub struct Event {
+    pub draw: usize,
+    pub number: i32,
+    pub marked: Vec<usize>,
+    pub winners: Vec<Winner>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Standing {
+    Won(Winner),
+    NeverWon { card: usize },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub events: Vec<Event>,
+    pub ranking: Vec<Standing>,
+}
+
+impl Outcome {
+    pub fn winners(&self) -> impl Iterator<Item = &Winner> {
+        self.ranking.iter().filter_map(|standing| match standing {
+            Standing::Won(winner) => Some(winner),
+            Standing::NeverWon { .. } => None,
+        })
+    }
+
+    pub fn first_winner(&self) -> Option<&Winner> {
+        self.winners().next()
+    }
+
+    pub fn last_winner(&self) -> Option<&Winner> {
+        self.winners().last()
+    }
+}
+
+pub struct Game {
     cards: Vec<Card>,
     numbers: Vec<i32>,
-    first_winner: Winner,
-    last_winner: Winner,
+    holders: HashMap<i32, Vec<usize>>,
 }
 
 impl Game {
-    fn build<T: AsRef<str>>(lines: &[T]) -> Self {
-        let mut iter = lines.into_iter();
-        let numbers: Vec<i32> = iter
-            .nth(0)
-            .unwrap()
-            .as_ref()
-            .split(",")
-            .map(|s| s.parse::<i32>().unwrap())
-            .collect();
-        let lines_length = iter.clone().len();
+    /// Reads the draw order from the first line and then cards separated by
+    /// blank lines. Cards may be any rectangular size, and trailing
+    /// whitespace or carriage returns are ignored.
+    pub fn build<T: AsRef<str>>(lines: &[T]) -> Result<Self, ParseError> {
+        let mut iter = lines.iter().map(|line| line.as_ref().trim_end());
+        let numbers = iter
+            .next()
+            .filter(|line| !line.trim().is_empty())
+            .ok_or(ParseError::MissingNumbers)?
+            .split(',')
+            .map(|s| parse_number(0, s.trim()))
+            .collect::<Result<Vec<i32>, _>>()?;
 
-        let (card_inputs, _) = iter
-            .map(|s| s.as_ref())
-            .enumerate()
-            .skip_while(|(_, s)| *s == "")
-            .fold((vec![], vec![]), |(mut cards, mut current), (i, line)| {
-                if line == "" {
-                    cards.push(current);
-
-                    (cards, vec![])
-                } else if i + 1 == lines_length {
-                    current.push(line);
-                    cards.push(current);
+        let mut card_inputs: Vec<(usize, Vec<&str>)> = vec![];
+        let mut in_card = false;
+        for (i, line) in iter.enumerate() {
+            if line.trim().is_empty() {
+                in_card = false;
+            } else if in_card {
+                card_inputs.last_mut().unwrap().1.push(line);
+            } else {
+                card_inputs.push((i + 1, vec![line]));
+                in_card = true;
+            }
+        }
 
-                    (cards, vec![])
-                } else {
-                    current.push(line);
+        let cards = card_inputs
+            .into_iter()
+            .map(|(start, rows)| Card::build(rows).map_err(|e| e.offset(start)))
+            .collect::<Result<Vec<_>, _>>()?;
 
-                    (cards, current)
-                }
-            });
+        Ok(Self::new(cards, numbers))
+    }
 
-        let cards: Vec<_> = card_inputs.into_iter().map(Card::build).collect();
+    fn new(cards: Vec<Card>, numbers: Vec<i32>) -> Self {
+        let mut holders: HashMap<i32, Vec<usize>> = HashMap::new();
+        for (i, card) in cards.iter().enumerate() {
+            for number in card.positions.keys() {
+                holders.entry(*number).or_default().push(i);
+            }
+        }
 
         Self {
             cards,
             numbers,
-            first_winner: Winner::new(),
-            last_winner: Winner::new(),
+            holders,
         }
     }
 
-    fn run(&mut self) {
-        let mut winners: Vec<Card> = vec![];
+    /// Replaces the standard row and column rules for every card. Custom
+    /// patterns must match the size of every card, since a card they don't
+    /// fit could never win by them.
+    pub fn with_rules(mut self, rules: &[WinRule]) -> Result<Self, RuleError> {
+        for rule in rules {
+            let WinRule::Pattern(mask) = rule else {
+                continue;
+            };
+            if !mask.iter().flatten().any(|&set| set) {
+                return Err(RuleError::EmptyPattern);
+            }
+
+            for (card, rows) in self.cards.iter().map(|card| &card.rows).enumerate() {
+                let (height, width) = (rows.len(), rows.first().map_or(0, |row| row.len()));
+                if mask.len() != height || mask.iter().any(|row| row.len() != width) {
+                    return Err(RuleError::PatternMismatch {
+                        card,
+                        height,
+                        width,
+                    });
+                }
+            }
+        }
+
+        for card in &mut self.cards {
+            card.apply_rules(rules);
+        }
+
+        Ok(self)
+    }
+
+    /// Plays every draw until all cards have won or the numbers run out,
+    /// logging each draw and ranking cards by the draw they won on.
+    pub fn run(&self) -> Outcome {
+        self.play(&self.numbers)
+    }
+
+    fn play(&self, numbers: &[i32]) -> Outcome {
         let mut cards = self.cards.clone();
-        let num_cards = cards.len();
+        let mut events = vec![];
+        let mut ranking = vec![];
 
-        'outer: for n in &self.numbers {
-            let mut next_cards: Vec<Card> = vec![];
+        for (draw, n) in numbers.iter().enumerate() {
+            let mut event = Event {
+                draw,
+                number: *n,
+                marked: vec![],
+                winners: vec![],
+            };
 
-            for mut card in cards {
-                card.mark(*n);
+            for &i in self.holders.get(n).into_iter().flatten() {
+                if cards[i].is_winner() {
+                    continue;
+                }
 
-                if let Some(_) = card.winning_number {
-                    winners.push(card.clone());
+                cards[i].mark(*n);
+                event.marked.push(i);
+                if cards[i].is_winner() {
+                    let winner = Winner::calculate_score(i, draw, &cards[i]);
+                    event.winners.push(winner.clone());
+                    ranking.push(Standing::Won(winner));
+                }
+            }
 
-                    if winners.len() == num_cards {
-                        break 'outer;
-                    }
-                } else {
-                    next_cards.push(card);
+            events.push(event);
+            if ranking.len() == cards.len() {
+                break;
+            }
+        }
+
+        ranking.extend(
+            cards
+                .iter()
+                .enumerate()
+                .filter(|(_, card)| !card.is_winner())
+                .map(|(card, _)| Standing::NeverWon { card }),
+        );
+
+        Outcome { events, ranking }
+    }
+
+    /// Plays `trials` games over shuffles of this game's numbers and reports
+    /// how often each card wins first or last. Cards tied on the deciding
+    /// draw share that trial's credit, so each column sums to one. With no
+    /// trials there is nothing to report.
+    pub fn analyze(&self, trials: usize, seed: u64) -> Vec<CardOdds> {
+        if trials == 0 {
+            return vec![];
+        }
+
+        let mut rng = Rng(seed);
+        let mut numbers = self.numbers.clone();
+        let mut odds: Vec<CardOdds> = (0..self.cards.len()).map(CardOdds::new).collect();
+        let mut turns = vec![(0, 0); self.cards.len()];
+
+        for _ in 0..trials {
+            rng.shuffle(&mut numbers);
+            let outcome = self.play(&numbers);
+            let winners: Vec<&Winner> = outcome.winners().collect();
+
+            for winner in &winners {
+                let (total, wins) = &mut turns[winner.card];
+                *total += winner.draw + 1;
+                *wins += 1;
+            }
+
+            if let (Some(first), Some(last)) = (winners.first(), winners.last()) {
+                let firsts: Vec<_> = winners.iter().filter(|w| w.draw == first.draw).collect();
+                let lasts: Vec<_> = winners.iter().filter(|w| w.draw == last.draw).collect();
+
+                for w in &firsts {
+                    odds[w.card].first += 1.0 / firsts.len() as f64;
+                }
+                for w in &lasts {
+                    odds[w.card].last += 1.0 / lasts.len() as f64;
                 }
             }
+        }
+
+        for (card, (total, wins)) in odds.iter_mut().zip(turns) {
+            card.first /= trials as f64;
+            card.last /= trials as f64;
+            if wins > 0 {
+                card.expected_turn = Some(total as f64 / wins as f64);
+            }
+        }
+
+        odds
+    }
+
+    /// Finds a shortest draw order, using only this game's numbers, after
+    /// which `card` is the first card to win or the last one to do so.
+    /// "First" means no other card wins on or before its winning draw, and
+    /// "last" means every other card has already won. Returns `None` when no
+    /// order of the available numbers can achieve that, or when there is no
+    /// such card. The search is exact, so `Goal::Last` is only practical for
+    /// fixture-sized sets of cards.
+    pub fn rig(&self, card: usize, goal: Goal) -> Option<Vec<i32>> {
+        if card >= self.cards.len() {
+            return None;
+        }
+
+        let pool: HashSet<i32> = self.numbers.iter().copied().collect();
+        let patterns: Vec<Vec<Vec<i32>>> = self
+            .cards
+            .iter()
+            .map(|c| {
+                let mut drawable: Vec<_> = c
+                    .pattern_numbers()
+                    .into_iter()
+                    .filter(|p| p.iter().all(|n| pool.contains(n)))
+                    .collect();
+                drawable.sort_by_key(|p| p.len());
+                drawable
+            })
+            .collect();
+
+        let order = match goal {
+            Goal::First => Self::rig_first(card, &patterns),
+            Goal::Last => Self::rig_last(card, &patterns),
+        }?;
+
+        let outcome = self.play(&order);
+        let winner = match goal {
+            Goal::First => outcome.first_winner(),
+            Goal::Last => outcome.last_winner(),
+        };
+        debug_assert_eq!(
+            winner.map(|w| (w.card, w.draw)),
+            Some((card, order.len() - 1))
+        );
+
+        Some(order)
+    }
+
+    fn rig_first(card: usize, patterns: &[Vec<Vec<i32>>]) -> Option<Vec<i32>> {
+        patterns[card]
+            .iter()
+            .find(|candidate| {
+                let drawn: HashSet<i32> = candidate.iter().copied().collect();
+                patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|&(other, _)| other != card)
+                    .all(|(_, other)| !other.iter().any(|p| covers(&drawn, p)))
+            })
+            .cloned()
+    }
+
+    fn rig_last(card: usize, patterns: &[Vec<Vec<i32>>]) -> Option<Vec<i32>> {
+        let others: Vec<usize> = (0..patterns.len()).filter(|&c| c != card).collect();
+        if patterns[card].is_empty() || others.iter().any(|&c| patterns[c].is_empty()) {
+            return None;
+        }
+
+        let mut search = LastSearch {
+            card,
+            others,
+            patterns,
+            order: vec![],
+            drawn: HashSet::new(),
+            best: None,
+        };
+        search.explore();
+
+        search.best
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    First,
+    Last,
+}
+
+fn covers(drawn: &HashSet<i32>, pattern: &[i32]) -> bool {
+    pattern.iter().all(|n| drawn.contains(n))
+}
+
+/// Branch and bound over which pattern each remaining card completes with.
+/// Every other card must be finished by the drawn numbers before the target
+/// has any full pattern, and the target is then finished as cheaply as
+/// possible.
+struct LastSearch<'a> {
+    card: usize,
+    others: Vec<usize>,
+    patterns: &'a [Vec<Vec<i32>>],
+    order: Vec<i32>,
+    drawn: HashSet<i32>,
+    best: Option<Vec<i32>>,
+}
+
+impl LastSearch<'_> {
+    fn explore(&mut self) {
+        let target = &self.patterns[self.card];
+        if target.iter().any(|p| covers(&self.drawn, p)) {
+            return;
+        }
+
+        // Every unfinished card needs at least its cheapest pattern's missing
+        // numbers, and the target needs at least one more draw after that.
+        // Branching on the most expensive card fails fastest.
+        let mut pending: Option<(usize, usize)> = None;
+        for &c in &self.others {
+            let cheapest = self.patterns[c]
+                .iter()
+                .map(|p| p.iter().filter(|n| !self.drawn.contains(n)).count())
+                .min()
+                .unwrap();
+            if cheapest > 0 && pending.is_none_or(|(_, most)| cheapest > most) {
+                pending = Some((c, cheapest));
+            }
+        }
+        let bound = self.order.len() + pending.map_or(0, |(_, most)| most) + 1;
+        if self.best.as_ref().is_some_and(|best| bound >= best.len()) {
+            return;
+        }
+
+        let Some((pending, _)) = pending else {
+            let finish = target
+                .iter()
+                .map(|p| self.missing(p))
+                .min_by_key(|missing| missing.len())
+                .unwrap();
+            let mut order = self.order.clone();
+            order.extend(finish);
+
+            if self
+                .best
+                .as_ref()
+                .is_none_or(|best| order.len() < best.len())
+            {
+                self.best = Some(order);
+            }
+            return;
+        };
+
+        let mut choices: Vec<Vec<i32>> = self.patterns[pending]
+            .iter()
+            .map(|p| self.missing(p))
+            .collect();
+        choices.sort_by_key(|missing| missing.len());
 
-            cards = next_cards.clone();
+        for missing in choices {
+            for &n in &missing {
+                self.order.push(n);
+                self.drawn.insert(n);
+            }
+
+            self.explore();
+
+            for n in &missing {
+                self.order.pop();
+                self.drawn.remove(n);
+            }
         }
+    }
 
-        let first_winner = winners.first().expect("No winners!");
-        let last_winner = winners.last().expect("No winners!");
+    fn missing(&self, pattern: &[i32]) -> Vec<i32> {
+        pattern
+            .iter()
+            .filter(|n| !self.drawn.contains(n))
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CardOdds {
+    pub card: usize,
+    pub first: f64,
+    pub last: f64,
+    pub expected_turn: Option<f64>,
+}
 
-        self.first_winner = Winner::calculate_score(first_winner);
-        self.last_winner = Winner::calculate_score(last_winner);
+impl CardOdds {
+    fn new(card: usize) -> Self {
+        Self {
+            card,
+            first: 0.0,
+            last: 0.0,
+            expected_turn: None,
+        }
     }
 }
 
-pub fn solve<T: AsRef<str>>(lines: &[T]) -> (usize, usize) {
-    let mut game = Game::build(lines);
-    game.run();
+impl fmt::Display for CardOdds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Card {}: wins first {:.2}%, wins last {:.2}%, ",
+            self.card + 1,
+            self.first * 100.0,
+            self.last * 100.0
+        )?;
 
-    (
-        game.first_winner.score as usize,
-        game.last_winner.score as usize,
-    )
+        match self.expected_turn {
+            Some(turn) => write!(f, "expected winning turn {:.2}", turn),
+            None => write!(f, "never wins"),
+        }
+    }
+}
+
+/// SplitMix64, so simulations are reproducible from a seed without pulling
+/// in a random number crate.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next() as u128 * bound as u128) >> 64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// A winning score, or a note that no card won at all.
+#[derive(Debug, PartialEq)]
+pub struct Score(pub Option<usize>);
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(score) => write!(f, "{}", score),
+            None => write!(f, "no card won"),
+        }
+    }
+}
+
+pub fn solve<T: AsRef<str>>(lines: &[T]) -> (Score, Score) {
+    let outcome = Game::build(lines).unwrap_or_else(|e| panic!("{}", e)).run();
+    let score = |winner: Option<&Winner>| Score(winner.map(|w| w.score as usize));
+
+    (score(outcome.first_winner()), score(outcome.last_winner()))
 }
 
 #[cfg(test)]
@@ -228,20 +844,17 @@ mod tests {
             vec![Square::new(10), Square::new(9)],
         ]);
 
-        let card = Card::build(vec!["14 21", "10  9"]);
+        let card = Card::build(vec!["14 21", "10  9"]).unwrap();
 
         assert_eq!(card, expected);
     }
 
     #[test]
-    fn card_cols() {
-        assert_eq!(
-            build_test_card().cols(),
-            vec![
-                vec![Square::new(14), Square::new(10)],
-                vec![Square::new(21), Square::new(9)],
-            ]
-        )
+    fn card_positions() {
+        let card = build_test_card();
+
+        assert_eq!(card.positions[&10], (1, 0));
+        assert_eq!(card.positions[&21], (0, 1));
     }
 
     #[test]
@@ -249,8 +862,11 @@ mod tests {
         let mut card = build_test_card();
 
         card.mark(9);
+        card.mark(9);
+        card.mark(99);
 
         assert_eq!(card.rows[1][1], Square::build(9, Status::Marked));
+        assert_eq!(card.pattern_marks, vec![0, 1, 0, 1]);
     }
 
     #[test]
@@ -280,6 +896,329 @@ mod tests {
         card.mark(21);
 
         assert!(card.is_winner());
+        assert_eq!(card.winning_number, Some(21));
+
+        card.mark(14);
+
+        assert_eq!(card.winning_number, Some(21));
+    }
+
+    #[test]
+    fn card_win_rules() {
+        let mut card = Card::build(vec!["1 2 3", "4 5 6", "7 8 9"]).unwrap();
+        card.apply_rules(&[WinRule::Diagonals]);
+        for n in [1, 2, 3, 5] {
+            card.mark(n);
+        }
+        assert!(!card.is_winner());
+        card.mark(7);
+        assert_eq!(card.winning_number, Some(7));
+
+        let mut card = Card::build(vec!["1 2 3", "4 5 6", "7 8 9"]).unwrap();
+        card.apply_rules(&[WinRule::FourCorners]);
+        for n in [1, 3, 7, 9] {
+            card.mark(n);
+        }
+        assert_eq!(card.winning_number, Some(9));
+
+        let mut card = Card::build(vec!["1 2", "3 4"]).unwrap();
+        card.apply_rules(&[WinRule::Blackout]);
+        for n in [1, 2, 3] {
+            card.mark(n);
+        }
+        assert!(!card.is_winner());
+        card.mark(4);
+        assert!(card.is_winner());
+    }
+
+    #[test]
+    fn card_custom_pattern() {
+        let plus = WinRule::parse_pattern(&[".#.", "###", ".#."]);
+        let mut card = Card::build(vec!["1 2 3", "4 5 6", "7 8 9"]).unwrap();
+        card.mark(2);
+        card.mark(5);
+        card.apply_rules(&[plus]);
+
+        assert_eq!(card.pattern_marks, vec![2]);
+
+        for n in [4, 6, 1] {
+            card.mark(n);
+        }
+        assert!(!card.is_winner());
+        card.mark(8);
+        assert!(card.is_winner());
+    }
+
+    #[test]
+    fn card_mismatched_pattern() {
+        let mut card = build_test_card();
+        card.apply_rules(&[WinRule::parse_pattern(&["#.#"]), WinRule::Diagonals]);
+
+        assert_eq!(card.pattern_sizes, vec![2, 2]);
+    }
+
+    #[test]
+    fn game_with_rules() {
+        let game = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["5 6", "7 8"]).unwrap(),
+            ],
+            vec![1, 6, 4, 7],
+        )
+        .with_rules(&[WinRule::Diagonals])
+        .unwrap();
+
+        let outcome = game.run();
+
+        assert_eq!(outcome.first_winner().unwrap().score, (2 + 3) * 4);
+        assert_eq!(outcome.last_winner().unwrap().score, (5 + 8) * 7);
+    }
+
+    #[test]
+    fn game_with_mismatched_pattern() {
+        let game = || {
+            Game::new(
+                vec![
+                    Card::build(vec!["1 2", "3 4"]).unwrap(),
+                    Card::build(vec!["5 6 9", "7 8 10"]).unwrap(),
+                ],
+                vec![1, 4],
+            )
+        };
+        let corners = WinRule::parse_pattern(&["#.", ".#"]);
+
+        let error = game().with_rules(&[corners]).err();
+        assert_eq!(
+            error,
+            Some(RuleError::PatternMismatch {
+                card: 1,
+                height: 2,
+                width: 3
+            })
+        );
+        assert_eq!(
+            error.unwrap().to_string(),
+            "Pattern doesn't fit card 2, which is 2x3!"
+        );
+        assert_eq!(
+            game()
+                .with_rules(&[WinRule::parse_pattern(&["..", ".."])])
+                .err(),
+            Some(RuleError::EmptyPattern)
+        );
+    }
+
+    #[test]
+    fn game_run_log() {
+        let game = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["2 5", "6 7"]).unwrap(),
+                Card::build(vec!["8 9", "10 11"]).unwrap(),
+            ],
+            vec![2, 4, 5, 1, 3],
+        );
+
+        let outcome = game.run();
+
+        assert_eq!(
+            outcome.events,
+            vec![
+                Event {
+                    draw: 0,
+                    number: 2,
+                    marked: vec![0, 1],
+                    winners: vec![],
+                },
+                Event {
+                    draw: 1,
+                    number: 4,
+                    marked: vec![0],
+                    winners: vec![Winner {
+                        card: 0,
+                        draw: 1,
+                        score: (1 + 3) * 4,
+                    }],
+                },
+                Event {
+                    draw: 2,
+                    number: 5,
+                    marked: vec![1],
+                    winners: vec![Winner {
+                        card: 1,
+                        draw: 2,
+                        score: (6 + 7) * 5,
+                    }],
+                },
+                Event {
+                    draw: 3,
+                    number: 1,
+                    marked: vec![],
+                    winners: vec![],
+                },
+                Event {
+                    draw: 4,
+                    number: 3,
+                    marked: vec![],
+                    winners: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            outcome.ranking,
+            vec![
+                Standing::Won(Winner {
+                    card: 0,
+                    draw: 1,
+                    score: 16,
+                }),
+                Standing::Won(Winner {
+                    card: 1,
+                    draw: 2,
+                    score: 65,
+                }),
+                Standing::NeverWon { card: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn game_run_without_winners() {
+        let game = Game::new(vec![build_test_card()], vec![14, 9]);
+
+        let outcome = game.run();
+
+        assert_eq!(outcome.first_winner(), None);
+        assert_eq!(outcome.ranking, vec![Standing::NeverWon { card: 0 }]);
+    }
+
+    #[test]
+    fn rng_shuffle() {
+        let mut numbers: Vec<i32> = (0..20).collect();
+
+        Rng(7).shuffle(&mut numbers);
+        let mut again: Vec<i32> = (0..20).collect();
+        Rng(7).shuffle(&mut again);
+
+        assert_eq!(numbers, again);
+        assert_ne!(numbers, (0..20).collect::<Vec<_>>());
+        numbers.sort();
+        assert_eq!(numbers, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn game_analyze() {
+        let game = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["5 6", "7 8"]).unwrap(),
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+            ],
+            (1..=8).collect(),
+        );
+
+        let odds = game.analyze(2000, 42);
+
+        assert_eq!(odds, game.analyze(2000, 42));
+        assert_eq!(odds[0].first, odds[2].first);
+        assert!((odds[0].first - 0.25).abs() < 0.05);
+        assert!((odds[1].first - 0.5).abs() < 0.05);
+        assert!((odds.iter().map(|o| o.first).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((odds.iter().map(|o| o.last).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(odds.iter().all(|o| o.expected_turn.unwrap() >= 2.0));
+    }
+
+    #[test]
+    fn game_analyze_never_wins() {
+        let game = Game::new(vec![build_test_card()], vec![14, 9]);
+
+        let odds = game.analyze(10, 1);
+
+        assert_eq!(odds, vec![CardOdds::new(0)]);
+        assert_eq!(
+            odds[0].to_string(),
+            "Card 1: wins first 0.00%, wins last 0.00%, never wins"
+        );
+        assert_eq!(game.analyze(0, 1), vec![]);
+    }
+
+    #[test]
+    fn game_rig_first() {
+        let game = Game::new(
+            vec![
+                Card::build(vec!["1 2 3", "4 5 6", "7 8 9"]).unwrap(),
+                Card::build(vec!["1 2 3", "10 11 12", "13 14 15"]).unwrap(),
+                Card::build(vec!["4 5 6", "16 17 18", "19 20 21"]).unwrap(),
+            ],
+            (1..=21).collect(),
+        );
+
+        let order = game.rig(0, Goal::First).unwrap();
+
+        assert_eq!(order.len(), 3);
+        assert!(!order.contains(&1) || !order.contains(&2) || !order.contains(&3));
+        let winner = game.play(&order);
+        assert_eq!(winner.first_winner().map(|w| w.card), Some(0));
+    }
+
+    #[test]
+    fn game_rig_last() {
+        let game = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["1 5", "6 7"]).unwrap(),
+                Card::build(vec!["2 8", "9 10"]).unwrap(),
+            ],
+            (1..=10).collect(),
+        );
+
+        let order = game.rig(0, Goal::Last).unwrap();
+
+        // Cards 1 and 2 can't both finish via 1 and 2 without finishing
+        // card 0 too, so one of them has to take a detour.
+        assert_eq!(order.len(), 5);
+        let outcome = game.play(&order);
+        assert_eq!(outcome.last_winner().map(|w| w.card), Some(0));
+        assert_eq!(outcome.winners().count(), 3);
+        assert_eq!(outcome.last_winner().unwrap().draw, order.len() - 1);
+    }
+
+    #[test]
+    fn game_rig_impossible() {
+        let twins = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+            ],
+            (1..=4).collect(),
+        );
+        let short = Game::new(
+            vec![
+                Card::build(vec!["1 2", "3 4"]).unwrap(),
+                Card::build(vec!["5 6", "7 8"]).unwrap(),
+            ],
+            vec![1, 2, 3, 4],
+        );
+
+        assert_eq!(twins.rig(0, Goal::First), None);
+        assert_eq!(twins.rig(2, Goal::First), None);
+        assert_eq!(short.rig(99, Goal::Last), None);
+        assert_eq!(twins.rig(1, Goal::Last), None);
+        assert_eq!(short.rig(0, Goal::Last), None);
+        assert_eq!(short.rig(1, Goal::First), None);
+    }
+
+    #[test]
+    fn game_holders() {
+        let game = Game::new(
+            vec![build_test_card(), Card::build(vec!["9 1", "2 3"]).unwrap()],
+            vec![9],
+        );
+
+        assert_eq!(game.holders[&9], vec![0, 1]);
+        assert_eq!(game.holders[&14], vec![0]);
+        assert_eq!(game.holders.get(&5), None);
     }
 
     #[test]
@@ -289,6 +1228,71 @@ mod tests {
         assert_eq!(card.unmarked(), vec![14, 21, 10, 9]);
     }
 
+    #[test]
+    fn card_building_errors() {
+        assert_eq!(
+            Card::build(vec!["1 2", "3"]),
+            Err(ParseError::RaggedRow { line: 1 })
+        );
+        assert_eq!(
+            Card::build(vec!["1 2", "3 1"]),
+            Err(ParseError::DuplicateNumber { line: 1, number: 1 })
+        );
+        assert_eq!(
+            Card::build(vec!["1 x"]),
+            Err(ParseError::InvalidNumber {
+                line: 0,
+                value: String::from("x"),
+            })
+        );
+    }
+
+    #[test]
+    fn game_build_mixed_sizes() {
+        let input = [
+            "3,1,2\r",
+            "\r",
+            "",
+            " 1  2  3 \r",
+            " 4  5  6\r",
+            "\r",
+            "7 8\r",
+            "9 1\r",
+            "2 3",
+            "",
+            "",
+        ];
+
+        let game = Game::build(&input).unwrap();
+
+        assert_eq!(game.numbers, vec![3, 1, 2]);
+        assert_eq!(
+            game.cards,
+            vec![
+                Card::build(vec!["1 2 3", "4 5 6"]).unwrap(),
+                Card::build(vec!["7 8", "9 1", "2 3"]).unwrap(),
+            ]
+        );
+        assert_eq!(game.run().first_winner().unwrap().card, 0);
+    }
+
+    #[test]
+    fn game_build_errors() {
+        let empty: [&str; 0] = [];
+
+        assert_eq!(Game::build(&empty).err(), Some(ParseError::MissingNumbers));
+        assert_eq!(
+            Game::build(&["1,2", "", "1 2", "3 4", "", "5 6", "7 8 9"]).err(),
+            Some(ParseError::RaggedRow { line: 6 })
+        );
+        assert_eq!(
+            Game::build(&["1,2", "", "5 5"])
+                .err()
+                .map(|e| e.to_string()),
+            Some(String::from("Number 5 repeated on line 3!"))
+        );
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = [
@@ -318,7 +1322,15 @@ mod tests {
 
         let solution = solve(&input);
 
-        assert_eq!(solution, (4512, 1924));
+        assert_eq!(solution, (Score(Some(4512)), Score(Some(1924))));
+    }
+
+    #[test]
+    fn solve_without_winners() {
+        let solution = solve(&["1,2", "", "1 3", "4 5"]);
+
+        assert_eq!(solution, (Score(None), Score(None)));
+        assert_eq!(solution.0.to_string(), "no card won");
     }
 
     fn build_test_card() -> Card {
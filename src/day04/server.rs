@@ -0,0 +1,362 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{Card, Game};
+
+/// How long a client may stall a write before it is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the listener is polled for new clients.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A connected client. Lines are queued to a writer thread of its own, so a
+/// slow client never holds up the lobby or anyone else's broadcasts.
+struct Player {
+    name: Option<String>,
+    outbox: mpsc::Sender<String>,
+}
+
+impl Player {
+    /// Starts the writer thread, which shuts the connection down once the
+    /// outbox is dropped and drained, or as soon as a write fails.
+    fn connect(stream: TcpStream) -> Self {
+        let (outbox, lines) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let mut writer = &stream;
+            for line in lines {
+                if writeln!(writer, "{}", line).is_err() {
+                    break;
+                }
+            }
+            let _ = stream.shutdown(Shutdown::Both);
+        });
+
+        Self { name: None, outbox }
+    }
+}
+
+struct Lobby {
+    cards: Vec<Card>,
+    players: HashMap<usize, Player>,
+    claims: HashMap<usize, usize>,
+    started: bool,
+}
+
+impl Lobby {
+    /// The replies to one line from client `id`, which may already have
+    /// been dropped while its reader was still busy.
+    fn handle(&mut self, id: usize, line: &str, start: &mpsc::Sender<()>) -> Vec<String> {
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        if !self.players.contains_key(&id) {
+            return vec![String::from("ERROR Not connected")];
+        }
+
+        match command.to_uppercase().as_str() {
+            "JOIN" if argument.trim().is_empty() => vec![String::from("ERROR Name required")],
+            "JOIN" => {
+                let name = argument.trim().to_string();
+                if let Some(player) = self.players.get_mut(&id) {
+                    player.name = Some(name.clone());
+                }
+
+                vec![format!("OK Welcome {}", name)]
+            }
+            "CARDS" => {
+                let free: Vec<String> = (1..=self.cards.len())
+                    .filter(|card| !self.claims.contains_key(&(card - 1)))
+                    .map(|card| card.to_string())
+                    .collect();
+
+                vec![format!("CARDS {}", free.join(","))]
+            }
+            "SHOW" => match self.card_index(argument) {
+                Some(card) => self.cards[card]
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let values: Vec<String> = row
+                            .iter()
+                            .map(|square| format!("{:>2}", square.value))
+                            .collect();
+                        format!("ROW {}", values.join(" "))
+                    })
+                    .collect(),
+                None => vec![String::from("ERROR No such card")],
+            },
+            "CLAIM" => vec![self.claim(id, argument)],
+            "START" if self.started => vec![String::from("ERROR Already started")],
+            "START" => {
+                self.started = true;
+                start.send(()).unwrap();
+
+                vec![String::from("OK Starting")]
+            }
+            _ => vec![format!("ERROR Unknown command {}", command)],
+        }
+    }
+
+    fn claim(&mut self, id: usize, argument: &str) -> String {
+        if self
+            .players
+            .get(&id)
+            .is_none_or(|player| player.name.is_none())
+        {
+            return String::from("ERROR Join first");
+        }
+        if self.started {
+            return String::from("ERROR Already started");
+        }
+
+        match self.card_index(argument) {
+            None => String::from("ERROR No such card"),
+            Some(card) if self.claims.contains_key(&card) => {
+                String::from("ERROR Card already claimed")
+            }
+            Some(card) => {
+                self.claims.insert(card, id);
+                format!("OK Claimed card {}", card + 1)
+            }
+        }
+    }
+
+    fn card_index(&self, argument: &str) -> Option<usize> {
+        argument
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|card| (1..=self.cards.len()).contains(card))
+            .map(|card| card - 1)
+    }
+
+    fn owner(&self, card: usize) -> &str {
+        self.claims
+            .get(&card)
+            .and_then(|id| self.players.get(id))
+            .and_then(|player| player.name.as_deref())
+            .unwrap_or("unclaimed")
+    }
+
+    fn send(&mut self, id: usize, lines: &[String]) {
+        let delivered = match self.players.get(&id) {
+            Some(player) => lines
+                .iter()
+                .all(|line| player.outbox.send(line.clone()).is_ok()),
+            None => true,
+        };
+
+        if !delivered {
+            self.players.remove(&id);
+        }
+    }
+
+    fn broadcast(&mut self, line: &str) {
+        let ids: Vec<usize> = self.players.keys().copied().collect();
+
+        for id in ids {
+            self.send(id, &[line.to_string()]);
+        }
+    }
+}
+
+/// Runs a game of bingo for everyone connected to `listener`.
+///
+/// Clients speak a line-based protocol: `JOIN <name>`, `CARDS`, `SHOW <card>`,
+/// `CLAIM <card>` and `START`. Once started, every draw is broadcast as
+/// `DRAW <turn> <number>`, winners as `WINNER <card> <player> <score>`, and
+/// the game finishes with `END`, after which the listener is closed. The
+/// draws and scores come straight from `Game::run`, so the server can never
+/// disagree with the engine.
+pub fn serve(game: Game, listener: TcpListener, interval: Duration) -> io::Result<()> {
+    let outcome = game.run();
+    let lobby = Arc::new(Mutex::new(Lobby {
+        cards: game.cards,
+        players: HashMap::new(),
+        claims: HashMap::new(),
+        started: false,
+    }));
+    let (start, started) = mpsc::channel();
+    let open = Arc::new(AtomicBool::new(true));
+
+    listener.set_nonblocking(true)?;
+    let accepting = Arc::clone(&lobby);
+    let still_open = Arc::clone(&open);
+    let acceptor = thread::spawn(move || {
+        let mut id = 0;
+        while still_open.load(Ordering::Relaxed) {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_INTERVAL);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let configured = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            let Ok(reader) = configured.and_then(|_| stream.try_clone()) else {
+                continue;
+            };
+            id += 1;
+
+            let mut guard = accepting.lock().unwrap();
+            let cards = guard.cards.len();
+            guard.players.insert(id, Player::connect(stream));
+            guard.send(id, &[format!("WELCOME {} cards", cards)]);
+            drop(guard);
+
+            let lobby = Arc::clone(&accepting);
+            let start = start.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(reader).lines() {
+                    let Ok(line) = line else { break };
+                    if line.trim().eq_ignore_ascii_case("QUIT") {
+                        break;
+                    }
+
+                    let mut lobby = lobby.lock().unwrap();
+                    // Dropped after a failed write, or because the game ended.
+                    if !lobby.players.contains_key(&id) {
+                        break;
+                    }
+                    let reply = lobby.handle(id, line.trim(), &start);
+                    lobby.send(id, &reply);
+                }
+
+                lobby.lock().unwrap().players.remove(&id);
+            });
+        }
+    });
+
+    if started.recv().is_err() {
+        return Ok(());
+    }
+
+    for event in &outcome.events {
+        thread::sleep(interval);
+
+        let mut lobby = lobby.lock().unwrap();
+        lobby.broadcast(&format!("DRAW {} {}", event.draw + 1, event.number));
+        for winner in &event.winners {
+            let line = format!(
+                "WINNER {} {} {}",
+                winner.card + 1,
+                lobby.owner(winner.card),
+                winner.score
+            );
+            lobby.broadcast(&line);
+        }
+    }
+
+    open.store(false, Ordering::Relaxed);
+    let mut lobby = lobby.lock().unwrap();
+    lobby.broadcast("END");
+    // Dropping the outboxes lets each writer finish and hang up.
+    lobby.players.clear();
+    drop(lobby);
+
+    acceptor.join().expect("Accept thread panicked!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(client: &mut TcpStream, reader: &mut impl BufRead, line: &str) -> String {
+        writeln!(client, "{}", line).unwrap();
+
+        read_line(reader)
+    }
+
+    fn read_line(reader: &mut impl BufRead) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+
+        line.trim_end().to_string()
+    }
+
+    #[test]
+    fn lobby_without_player() {
+        let game = Game::build(&["1", "", "1"]).unwrap();
+        let mut lobby = Lobby {
+            cards: game.cards,
+            players: HashMap::new(),
+            claims: HashMap::new(),
+            started: false,
+        };
+        let (start, _) = mpsc::channel();
+
+        for line in ["JOIN ada", "CLAIM 1", "CARDS"] {
+            assert_eq!(
+                lobby.handle(1, line, &start),
+                vec![String::from("ERROR Not connected")]
+            );
+        }
+        assert_eq!(lobby.claim(1, "1"), "ERROR Join first");
+        assert!(lobby.claims.is_empty());
+    }
+
+    #[test]
+    fn serve_game() {
+        let game = Game::build(&["1,2,5,6,3", "", "1 2", "3 4", "", "5 6", "7 8"]).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || serve(game, listener, Duration::ZERO));
+        let mut idle = TcpStream::connect(address).unwrap();
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+
+        assert_eq!(read_line(&mut reader), "WELCOME 2 cards");
+        assert_eq!(
+            request(&mut client, &mut reader, "CLAIM 2"),
+            "ERROR Join first"
+        );
+        assert_eq!(
+            request(&mut client, &mut reader, "JOIN ada"),
+            "OK Welcome ada"
+        );
+        assert_eq!(request(&mut client, &mut reader, "SHOW 2"), "ROW  5  6");
+        assert_eq!(read_line(&mut reader), "ROW  7  8");
+        assert_eq!(
+            request(&mut client, &mut reader, "CLAIM 2"),
+            "OK Claimed card 2"
+        );
+        assert_eq!(
+            request(&mut client, &mut reader, "CLAIM 2"),
+            "ERROR Card already claimed"
+        );
+        assert_eq!(request(&mut client, &mut reader, "CARDS"), "CARDS 1");
+        assert_eq!(request(&mut client, &mut reader, "START"), "OK Starting");
+
+        let broadcast: Vec<String> = reader.lines().map(Result::unwrap).collect();
+        server.join().unwrap().unwrap();
+
+        assert_eq!(read_line(&mut BufReader::new(&mut idle)), "WELCOME 2 cards");
+        assert!(TcpStream::connect(address).is_err());
+
+        assert_eq!(
+            broadcast,
+            vec![
+                "DRAW 1 1",
+                "DRAW 2 2",
+                "WINNER 1 unclaimed 14",
+                "DRAW 3 5",
+                "DRAW 4 6",
+                "WINNER 2 ada 90",
+                "END",
+            ]
+        );
+    }
+}
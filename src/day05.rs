@@ -1,21 +1,34 @@
-use std::cmp::{max, max_by, min_by};
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 
-#[derive(Debug, PartialEq)]
-struct Coord {
-    x: usize,
-    y: usize,
+mod export;
+mod sweep;
+
+pub use export::ExportError;
+
+/// Maps whose area is more than this many times the number of points the
+/// lines cover are stored sparsely.
+const MAX_DENSE_RATIO: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coord {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl Coord {
-    fn new(x: usize, y: usize) -> Self {
+    pub fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
 
     fn from_str(input: &str) -> Self {
-        let [x, y]: [usize; 2] = input
+        let [x, y]: [i64; 2] = input
             .split(",")
-            .map(|n| n.parse::<usize>().expect("Not a number!"))
-            .collect::<Vec<usize>>()
+            .map(|n| n.trim().parse::<i64>().expect("Not a number!"))
+            .collect::<Vec<i64>>()
             .try_into()
             .unwrap();
 
@@ -23,21 +36,21 @@ impl Coord {
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Line {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
     start: Coord,
     end: Coord,
 }
 
 impl Line {
-    fn new((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> Self {
+    pub fn new((x1, y1): (i64, i64), (x2, y2): (i64, i64)) -> Self {
         Self {
             start: Coord::new(x1, y1),
             end: Coord::new(x2, y2),
         }
     }
 
-    fn parse(input: &str) -> Self {
+    pub fn parse(input: &str) -> Self {
         let [start, end]: [Coord; 2] = input
             .split(" -> ")
             .map(Coord::from_str)
@@ -48,98 +61,466 @@ impl Line {
         Self { start, end }
     }
 
-    fn points(&self) -> Vec<Coord> {
-        let start = min_by(&self.start, &self.end, |c1, c2| c1.x.cmp(&c2.x));
-        let end = max_by(&self.start, &self.end, |c1, c2| c1.x.cmp(&c2.x));
-        let x_range = (start.x..=end.x).into_iter();
+    /// The endpoints ordered by x, then y, so both directions of a line
+    /// produce the same points in the same order.
+    fn ends(&self) -> (&Coord, &Coord) {
+        if (self.start.x, self.start.y) <= (self.end.x, self.end.y) {
+            (&self.start, &self.end)
+        } else {
+            (&self.end, &self.start)
+        }
+    }
 
-        if self.is_diagonal() {
-            let increasing = start.y < end.y;
+    /// Every integer point that lies exactly on the segment, stepping by the
+    /// direction reduced by the gcd of its components.
+    pub fn points(&self) -> Vec<Coord> {
+        let (start, end) = self.ends();
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+        if steps == 0 {
+            return vec![Coord::new(start.x, start.y)];
+        }
+        let (step_x, step_y) = (dx / steps, dy / steps);
 
-            x_range
-                .enumerate()
-                .map(|(i, x)| {
-                    let y = if increasing { start.y + i } else { start.y - i };
-                    Coord::new(x, y)
-                })
-                .collect()
+        (0..=steps)
+            .map(|i| Coord::new(start.x + i * step_x, start.y + i * step_y))
+            .collect()
+    }
+
+    /// One point per step along the major axis, as Bresenham's algorithm
+    /// would draw the segment on a raster.
+    pub fn bresenham(&self) -> Vec<Coord> {
+        let (start, end) = self.ends();
+        let (dx, dy) = ((end.x - start.x).abs(), -(end.y - start.y).abs());
+        let step_y = if start.y < end.y { 1 } else { -1 };
+        let (mut x, mut y) = (start.x, start.y);
+        let mut error = dx + dy;
+        let mut points = vec![];
+
+        loop {
+            points.push(Coord::new(x, y));
+            if x == end.x && y == end.y {
+                break points;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += 1;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    fn raster(&self, raster: Raster) -> Vec<Coord> {
+        match raster {
+            Raster::Exact => self.points(),
+            Raster::Bresenham => self.bresenham(),
+        }
+    }
+
+    fn contains(&self, point: &Coord, raster: Raster) -> bool {
+        let (start, end) = self.ends();
+        let within = (start.x..=end.x).contains(&point.x)
+            && (min(start.y, end.y)..=max(start.y, end.y)).contains(&point.y);
+
+        match raster {
+            _ if !within => false,
+            Raster::Exact => {
+                (end.x - start.x) * (point.y - start.y) == (end.y - start.y) * (point.x - start.x)
+            }
+            Raster::Bresenham => self.bresenham().contains(point),
+        }
+    }
+
+    fn len(&self) -> usize {
+        max(
+            self.start.x.abs_diff(self.end.x),
+            self.start.y.abs_diff(self.end.y),
+        ) as usize
+            + 1
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        let (dx, dy) = (self.end.x - self.start.x, self.end.y - self.start.y);
+
+        if dy == 0 {
+            Orientation::Horizontal
+        } else if dx == 0 {
+            Orientation::Vertical
+        } else if dx.abs() == dy.abs() {
+            Orientation::Diagonal
         } else {
-            let y_range = if start.y < end.y {
-                start.y..=end.y
-            } else {
-                end.y..=start.y
-            };
-            x_range
-                .flat_map(|x| y_range.clone().into_iter().map(move |y| Coord::new(x, y)))
-                .collect()
+            Orientation::Other
         }
     }
 
-    fn is_diagonal(&self) -> bool {
-        self.start.x != self.end.x && self.start.y != self.end.y
+    fn is_axis_aligned(&self) -> bool {
+        matches!(
+            self.orientation(),
+            Orientation::Horizontal | Orientation::Vertical
+        )
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    Other,
+}
+
+/// How lines that aren't horizontal, vertical or 45° become points: only
+/// the lattice points exactly on them, or a Bresenham approximation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Raster {
+    #[default]
+    Exact,
+    Bresenham,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Grid {
+    Dense(Vec<Vec<usize>>),
+    Sparse(HashMap<(i64, i64), usize>),
+}
+
+/// Vent counts for every point in a `width` × `height` area starting at
+/// `origin`, which moves below zero when lines have negative coordinates.
+/// A dense grid is stored row-major, `points[y][x]` relative to `origin`,
+/// like the puzzle's diagram; a sparse one grows its area to fit the points
+/// drawn. The lines drawn so far are kept, numbered in the order they were
+/// given to `add_lines`, so points can be traced back to them.
 #[derive(Debug, PartialEq)]
-struct Map {
-    points: Vec<Vec<usize>>,
+pub struct Map {
+    origin: Coord,
+    width: usize,
+    height: usize,
+    raster: Raster,
+    points: Grid,
+    lines: Vec<(usize, Line)>,
+    lines_seen: usize,
 }
 
 impl Map {
-    fn new(x: usize, y: usize) -> Self {
+    fn new(width: usize, height: usize) -> Self {
         Self {
-            points: vec![vec![0; y]; x],
+            width,
+            height,
+            points: Grid::Dense(vec![vec![0; width]; height]),
+            ..Self::sparse()
         }
     }
 
-    fn build(lines: &Vec<Line>) -> Self {
-        let (max_x, max_y): (usize, usize) = lines.iter().fold((0, 0), |acc, line| {
-            (
-                max(max(line.start.x, line.end.x) + 1, acc.0),
-                max(max(line.start.y, line.end.y) + 1, acc.1),
-            )
-        });
+    fn sparse() -> Self {
+        Self {
+            origin: Coord::new(0, 0),
+            width: 0,
+            height: 0,
+            raster: Raster::default(),
+            points: Grid::Sparse(HashMap::new()),
+            lines: vec![],
+            lines_seen: 0,
+        }
+    }
+
+    /// Picks a dense grid that just fits every line, unless that grid would
+    /// be mostly empty compared to the points the lines cover.
+    pub fn build(lines: &[Line]) -> Self {
+        let Some(first) = lines.first() else {
+            return Self::sparse();
+        };
+        let seed = (
+            (first.start.x, first.start.y),
+            (first.start.x, first.start.y),
+        );
+        let ((min_x, min_y), (max_x, max_y)) = lines
+            .iter()
+            .flat_map(|line| [&line.start, &line.end])
+            .fold(seed, |((min_x, min_y), (max_x, max_y)), c| {
+                (
+                    (min(min_x, c.x), min(min_y, c.y)),
+                    (max(max_x, c.x), max(max_y, c.y)),
+                )
+            });
+        let width = (max_x.abs_diff(min_x) + 1) as usize;
+        let height = (max_y.abs_diff(min_y) + 1) as usize;
+        let covered: usize = lines.iter().map(Line::len).sum();
 
-        Self::new(max_x, max_y)
+        match width.checked_mul(height) {
+            Some(area) if area <= covered.saturating_mul(MAX_DENSE_RATIO) => {
+                let origin = Coord::new(min_x, min_y);
+
+                Self {
+                    origin,
+                    ..Self::new(width, height)
+                }
+            }
+            _ => Self::sparse(),
+        }
+    }
+
+    pub fn with_raster(self, raster: Raster) -> Self {
+        Self { raster, ..self }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    fn add_lines(&mut self, lines: &Vec<Line>, include_diagonals: bool) {
+    pub fn add_lines(&mut self, lines: &[Line], include_diagonals: bool) {
         for line in lines {
-            if !line.is_diagonal() || include_diagonals {
-                for point in line.points() {
-                    self.points[point.x][point.y] += 1;
+            self.lines_seen += 1;
+            if line.is_axis_aligned() || include_diagonals {
+                self.lines.push((self.lines_seen - 1, line.clone()));
+                for point in line.raster(self.raster) {
+                    match &mut self.points {
+                        Grid::Dense(points) => {
+                            let x = (point.x - self.origin.x) as usize;
+                            let y = (point.y - self.origin.y) as usize;
+                            points[y][x] += 1;
+                        }
+                        Grid::Sparse(points) => {
+                            *points.entry((point.x, point.y)).or_default() += 1;
+                            self.grow(&point);
+                        }
+                    }
                 }
             }
         }
     }
 
-    fn intersection_count(&self) -> usize {
-        self.points
-            .iter()
-            .flat_map(|x| x.iter().map(|y| *y > 1))
-            .filter(|i| *i)
+    /// Stretches a sparse map's area to take in `point`.
+    fn grow(&mut self, point: &Coord) {
+        if self.width == 0 {
+            (self.origin, self.width, self.height) = (point.clone(), 1, 1);
+            return;
+        }
+
+        let end_x = max(point.x, self.origin.x + self.width as i64 - 1);
+        let end_y = max(point.y, self.origin.y + self.height as i64 - 1);
+        self.origin = Coord::new(min(point.x, self.origin.x), min(point.y, self.origin.y));
+        self.width = (end_x - self.origin.x + 1) as usize;
+        self.height = (end_y - self.origin.y + 1) as usize;
+    }
+
+    /// How many lines cover `point`, which is zero outside the map.
+    pub fn count(&self, point: &Coord) -> usize {
+        match &self.points {
+            Grid::Dense(points) => {
+                let x = usize::try_from(point.x - self.origin.x).ok();
+                let y = usize::try_from(point.y - self.origin.y).ok();
+                x.zip(y)
+                    .and_then(|(x, y)| points.get(y)?.get(x))
+                    .copied()
+                    .unwrap_or(0)
+            }
+            Grid::Sparse(points) => points.get(&(point.x, point.y)).copied().unwrap_or(0),
+        }
+    }
+
+    /// The counts along each row, from the top of the diagram down.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<usize>> + '_ {
+        (0..self.height as i64).map(move |y| {
+            (0..self.width as i64)
+                .map(|x| self.count(&Coord::new(self.origin.x + x, self.origin.y + y)))
+                .collect()
+        })
+    }
+
+    /// The counts down each column, from the left of the diagram across.
+    pub fn columns(&self) -> impl Iterator<Item = Vec<usize>> + '_ {
+        (0..self.width as i64).map(move |x| {
+            (0..self.height as i64)
+                .map(|y| self.count(&Coord::new(self.origin.x + x, self.origin.y + y)))
+                .collect()
+        })
+    }
+
+    /// The x and y coordinates `Display` draws, with `None` for a gap: the
+    /// whole area of a dense map, but only the occupied columns and rows of
+    /// a sparse one, whose area is usually far too big to draw.
+    fn drawn_axes(&self) -> (Vec<Option<i64>>, Vec<Option<i64>>) {
+        let Grid::Sparse(points) = &self.points else {
+            return (
+                (0..self.width as i64)
+                    .map(|x| Some(self.origin.x + x))
+                    .collect(),
+                (0..self.height as i64)
+                    .map(|y| Some(self.origin.y + y))
+                    .collect(),
+            );
+        };
+
+        let with_gaps = |mut axis: Vec<i64>| {
+            axis.sort_unstable();
+            axis.dedup();
+
+            let mut drawn = vec![];
+            for (i, coordinate) in axis.iter().enumerate() {
+                if i > 0 && coordinate - axis[i - 1] > 1 {
+                    drawn.push(None);
+                }
+                drawn.push(Some(*coordinate));
+            }
+            drawn
+        };
+
+        (
+            with_gaps(points.keys().map(|(x, _)| *x).collect()),
+            with_gaps(points.keys().map(|(_, y)| *y).collect()),
+        )
+    }
+
+    pub fn intersection_count(&self) -> usize {
+        self.count_at_least(2)
+    }
+
+    /// Every point covered by at least one line, with its line count.
+    fn covered(&self) -> Box<dyn Iterator<Item = (Coord, usize)> + '_> {
+        let points: Box<dyn Iterator<Item = (Coord, usize)>> = match &self.points {
+            Grid::Dense(points) => Box::new(points.iter().enumerate().flat_map(move |(y, row)| {
+                row.iter().enumerate().map(move |(x, count)| {
+                    let point = Coord::new(self.origin.x + x as i64, self.origin.y + y as i64);
+                    (point, *count)
+                })
+            })),
+            Grid::Sparse(points) => Box::new(
+                points
+                    .iter()
+                    .map(|(&(x, y), &count)| (Coord::new(x, y), count)),
+            ),
+        };
+
+        Box::new(points.filter(|(_, count)| *count > 0))
+    }
+
+    /// How many points are covered by at least `threshold` lines.
+    pub fn count_at_least(&self, threshold: usize) -> usize {
+        self.covered()
+            .filter(|(_, count)| *count >= threshold)
             .count()
     }
+
+    /// The highest line count on the map and every point that reaches it,
+    /// ordered by x then y.
+    pub fn max_coverage(&self) -> Option<(usize, Vec<Coord>)> {
+        let highest = self.covered().map(|(_, count)| count).max()?;
+        let mut points: Vec<Coord> = self
+            .covered()
+            .filter(|(_, count)| *count == highest)
+            .map(|(point, _)| point)
+            .collect();
+        points.sort_by_key(|point| (point.x, point.y));
+
+        Some((highest, points))
+    }
+
+    /// How many points are covered by each line count.
+    pub fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for (_, count) in self.covered() {
+            *histogram.entry(count).or_default() += 1;
+        }
+
+        histogram
+    }
+
+    /// The numbers of the drawn lines that pass through `point`.
+    pub fn lines_at(&self, point: &Coord) -> Vec<usize> {
+        self.lines
+            .iter()
+            .filter(|(_, line)| line.contains(point, self.raster))
+            .map(|(index, _)| *index)
+            .collect()
+    }
 }
 
-pub fn solve<T: AsRef<str>>(input: &[T]) -> (usize, usize) {
-    let lines: Vec<Line> = input.into_iter().map(|s| Line::parse(s.as_ref())).collect();
+/// The map drawn like the puzzle's diagram: one row per `y`, `.` for
+/// points no line covers, the line count where it's a single digit and `+`
+/// for ten or more. Sparse maps leave out the rows and columns no line
+/// touches, drawing each run of them as one row or column of `~`.
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (xs, ys) = self.drawn_axes();
 
-    (part_one(&lines), part_two(&lines))
+        for y in &ys {
+            for x in &xs {
+                let cell = match (x, y) {
+                    (Some(x), Some(y)) => match self.count(&Coord::new(*x, *y)) {
+                        0 => '.',
+                        count @ 1..=9 => char::from_digit(count as u32, 10).unwrap(),
+                        _ => '+',
+                    },
+                    _ => '~',
+                };
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How overlaps are counted: by drawing every line onto a `Map`, or from
+/// segment crossings and collinear overlaps without a grid, which doesn't
+/// slow down as lines get longer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Raster,
+    Sweep,
 }
 
-fn part_one(lines: &Vec<Line>) -> usize {
-    let mut map = Map::build(&lines);
-    map.add_lines(lines, false);
+/// The number of points covered by at least two of `lines`, counting exact
+/// lattice points for lines that aren't horizontal, vertical or 45°.
+pub fn count_overlaps(lines: &[Line], include_diagonals: bool, method: Method) -> usize {
+    match method {
+        Method::Raster => {
+            let mut map = Map::build(lines);
+            map.add_lines(lines, include_diagonals);
 
-    map.intersection_count()
+            map.intersection_count()
+        }
+        Method::Sweep => {
+            let included: Vec<&Line> = lines
+                .iter()
+                .filter(|line| line.is_axis_aligned() || include_diagonals)
+                .collect();
+
+            sweep::overlap_count(&included)
+        }
+    }
 }
 
-fn part_two(lines: &Vec<Line>) -> usize {
-    let mut map = Map::build(&lines);
-    map.add_lines(lines, true);
+pub fn solve<T: AsRef<str>>(input: &[T]) -> (usize, usize) {
+    let lines: Vec<Line> = input.iter().map(|s| Line::parse(s.as_ref())).collect();
+
+    (part_one(&lines), part_two(&lines))
+}
 
-    map.intersection_count()
+fn part_one(lines: &[Line]) -> usize {
+    count_overlaps(lines, false, Method::Raster)
+}
+
+fn part_two(lines: &[Line]) -> usize {
+    count_overlaps(lines, true, Method::Raster)
 }
 
 #[cfg(test)]
@@ -196,31 +577,174 @@ mod tests {
     }
 
     #[test]
-    fn line_is_diagonal() {
+    fn line_orientation() {
         let vertical_line = Line::new((0, 1), (0, 2));
         let horizontal_line = Line::new((0, 1), (2, 1));
         let diagonal_line = Line::new((0, 1), (1, 2));
+        let steep_line = Line::new((0, 1), (1, 3));
+
+        assert_eq!(vertical_line.orientation(), Orientation::Vertical);
+        assert_eq!(horizontal_line.orientation(), Orientation::Horizontal);
+        assert_eq!(diagonal_line.orientation(), Orientation::Diagonal);
+        assert_eq!(steep_line.orientation(), Orientation::Other);
+        assert!(vertical_line.is_axis_aligned());
+        assert!(!diagonal_line.is_axis_aligned());
+    }
+
+    #[test]
+    fn line_points_any_slope() {
+        let line = Line::new((6, 4), (0, 0));
+
+        assert_eq!(
+            line.points(),
+            vec![Coord::new(0, 0), Coord::new(3, 2), Coord::new(6, 4)]
+        );
+        assert_eq!(Line::new((2, 2), (2, 2)).points(), vec![Coord::new(2, 2)]);
+    }
+
+    #[test]
+    fn line_bresenham() {
+        let line = Line::new((0, 0), (4, 2));
+
+        assert_eq!(
+            line.bresenham(),
+            vec![
+                Coord::new(0, 0),
+                Coord::new(1, 1),
+                Coord::new(2, 1),
+                Coord::new(3, 2),
+                Coord::new(4, 2),
+            ]
+        );
+        assert_eq!(
+            Line::new((1, 3), (0, 0)).bresenham(),
+            vec![
+                Coord::new(0, 0),
+                Coord::new(0, 1),
+                Coord::new(1, 2),
+                Coord::new(1, 3),
+            ]
+        );
+        let diagonal_line = Line::new((9, 7), (7, 9));
+        assert_eq!(diagonal_line.bresenham(), diagonal_line.points());
+    }
+
+    #[test]
+    fn map_raster_modes() {
+        let lines = vec![Line::new((0, 0), (4, 2)), Line::new((0, 1), (4, 1))];
+        let mut exact = Map::new(5, 3);
+        let mut bresenham = Map::new(5, 3).with_raster(Raster::Bresenham);
+
+        exact.add_lines(&lines, true);
+        bresenham.add_lines(&lines, true);
+
+        assert_eq!(exact.intersection_count(), 1);
+        assert_eq!(bresenham.intersection_count(), 2);
+    }
+
+    #[test]
+    fn coord_from_str_negative() {
+        assert_eq!(Coord::from_str("-3,12"), Coord::new(-3, 12));
+    }
+
+    #[test]
+    fn line_points_negative() {
+        let diagonal_line = Line::new((-1, 0), (1, -2));
+
+        assert_eq!(
+            diagonal_line.points(),
+            vec![Coord::new(-1, 0), Coord::new(0, -1), Coord::new(1, -2)]
+        );
+    }
+
+    #[test]
+    fn map_build_offset() {
+        let lines = vec![Line::new((-2, -2), (2, 2)), Line::new((-2, 2), (2, -2))];
+        let mut map = Map::build(&lines);
+
+        assert_eq!(map.origin, Coord::new(-2, -2));
+
+        map.add_lines(&lines, true);
 
-        assert!(!vertical_line.is_diagonal());
-        assert!(!horizontal_line.is_diagonal());
-        assert!(diagonal_line.is_diagonal());
+        assert_eq!(map.intersection_count(), 1);
+        assert!(matches!(map.points, Grid::Dense(_)));
+        assert_eq!(map.count(&Coord::new(0, 0)), 2);
+        assert_eq!(map.to_string(), "1...1\n.1.1.\n..2..\n.1.1.\n1...1\n");
     }
 
     #[test]
     fn map_new() {
         let map = Map::new(2, 3);
 
-        assert_eq!(map.points, vec![vec![0; 3]; 2]);
+        assert_eq!((map.width(), map.height()), (2, 3));
+        assert_eq!(map.points, Grid::Dense(vec![vec![0; 2]; 3]));
+        assert_eq!(map.to_string(), "..\n..\n..\n");
     }
 
     #[test]
     fn map_build() {
-        let map = Map::build(&vec![Line::new((1, 1), (1, 8)), Line::new((7, 2), (3, 2))]);
+        let map = Map::build(&[Line::new((1, 1), (1, 8)), Line::new((7, 2), (3, 2))]);
+        let Grid::Dense(points) = map.points else {
+            panic!("Expected a dense map!");
+        };
 
-        for point in map.points.iter() {
-            assert_eq!(point.len(), 9);
+        for row in points.iter() {
+            assert_eq!(row.len(), 7);
         }
-        assert_eq!(map.points.len(), 8);
+        assert_eq!(points.len(), 8);
+        assert_eq!((map.width, map.height), (7, 8));
+        assert_eq!(map.origin, Coord::new(1, 1));
+    }
+
+    #[test]
+    fn map_build_far_from_origin() {
+        let lines = vec![
+            Line::new((1_000_000, 1_000_000), (1_000_003, 1_000_000)),
+            Line::new((1_000_001, 999_999), (1_000_001, 1_000_002)),
+        ];
+        let mut map = Map::build(&lines);
+
+        assert!(matches!(map.points, Grid::Dense(_)));
+        assert_eq!(map.origin, Coord::new(1_000_000, 999_999));
+        assert_eq!((map.width(), map.height()), (4, 4));
+
+        map.add_lines(&lines, false);
+
+        assert_eq!(map.intersection_count(), 1);
+        assert_eq!(
+            map.to_string(),
+            ".1..
+1211
+.1..
+.1..
+"
+        );
+        assert!(matches!(Map::build(&[]).points, Grid::Sparse(_)));
+    }
+
+    #[test]
+    fn map_build_sparse() {
+        let lines = vec![
+            Line::new((0, 0), (2, 0)),
+            Line::new((1_000_000, 1_000_000), (1_000_000, 999_998)),
+            Line::new((1_000_000, 999_999), (999_999, 999_999)),
+            Line::new((-1_000_000, 0), (-1_000_000, 1)),
+        ];
+        let mut map = Map::build(&lines);
+
+        assert_eq!(map.points, Grid::Sparse(HashMap::new()));
+        assert_eq!((map.width(), map.height()), (0, 0));
+
+        map.add_lines(&lines, false);
+
+        assert_eq!(map.intersection_count(), 1);
+        assert_eq!(map.origin, Coord::new(-1_000_000, 0));
+        assert_eq!((map.width(), map.height()), (2_000_001, 1_000_001));
+        assert_eq!(
+            map.to_string(),
+            "1~111~..\n1~...~..\n~~~~~~~~\n.~...~.1\n.~...~12\n.~...~.1\n"
+        );
+        assert_eq!(map.rows().next().unwrap().len(), map.width());
     }
 
     #[test]
@@ -228,7 +752,7 @@ mod tests {
         let mut map = Map::new(4, 4);
 
         map.add_lines(
-            &vec![
+            &[
                 Line::new((1, 1), (1, 3)),
                 Line::new((1, 2), (3, 2)),
                 Line::new((2, 1), (3, 3)),
@@ -236,8 +760,18 @@ mod tests {
             false,
         );
 
+        assert_eq!(map.to_string(), "....\n.1..\n.211\n.1..\n");
+        assert_eq!(
+            map.rows().collect::<Vec<_>>(),
+            vec![
+                vec![0, 0, 0, 0],
+                vec![0, 1, 0, 0],
+                vec![0, 2, 1, 1],
+                vec![0, 1, 0, 0],
+            ]
+        );
         assert_eq!(
-            map.points,
+            map.columns().collect::<Vec<_>>(),
             vec![
                 vec![0, 0, 0, 0],
                 vec![0, 1, 2, 1],
@@ -245,6 +779,25 @@ mod tests {
                 vec![0, 0, 1, 0],
             ]
         );
+
+        map.add_lines(&vec![Line::new((0, 0), (1, 0)); 10], false);
+        assert_eq!(map.to_string(), "++..\n.1..\n.211\n.1..\n");
+    }
+
+    #[test]
+    fn map_sparse_matches_dense() {
+        let lines: Vec<Line> = ["0,9 -> 5,9", "8,0 -> 0,8", "9,4 -> 3,4", "2,2 -> 2,1"]
+            .into_iter()
+            .map(Line::parse)
+            .collect();
+        let mut dense = Map::new(10, 10);
+        let mut sparse = Map::sparse();
+
+        dense.add_lines(&lines, true);
+        sparse.add_lines(&lines, true);
+
+        assert_eq!(dense.intersection_count(), sparse.intersection_count());
+        assert_eq!(dense.to_string(), sparse.to_string());
     }
 
     #[test]
@@ -252,7 +805,7 @@ mod tests {
         let mut map = Map::new(4, 4);
 
         map.add_lines(
-            &vec![
+            &[
                 Line::new((1, 1), (1, 3)),
                 Line::new((1, 2), (3, 2)),
                 Line::new((0, 3), (3, 3)),
@@ -260,13 +813,218 @@ mod tests {
             false,
         );
 
-        // 0 0 0 0
-        // 0 1 2 1
-        // 0 0 1 0
-        // 1 1 2 1
+        assert_eq!(map.to_string(), "....\n.1..\n.211\n1211\n");
         assert_eq!(map.intersection_count(), 2);
     }
 
+    #[test]
+    fn count_overlaps_methods_agree() {
+        let mut seed: u64 = 5;
+        let mut next = |range: i64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) as i64).rem_euclid(range) - range / 2
+        };
+        let mut lines: Vec<Line> = (0..120)
+            .map(|_| Line::new((next(30), next(30)), (next(30), next(30))))
+            .collect();
+        lines.extend((0..40).map(|_| {
+            let (x, y, length) = (next(30), next(30), next(20));
+            match next(4) {
+                -2 => Line::new((x, y), (x + length, y)),
+                -1 => Line::new((x, y), (x, y + length)),
+                0 => Line::new((x, y), (x + length, y + length)),
+                _ => Line::new((x, y), (x + length, y - length)),
+            }
+        }));
+        lines.push(Line::new((3, 3), (3, 3)));
+        lines.push(Line::new((3, 3), (3, 3)));
+
+        for include_diagonals in [false, true] {
+            assert_eq!(
+                count_overlaps(&lines, include_diagonals, Method::Sweep),
+                count_overlaps(&lines, include_diagonals, Method::Raster)
+            );
+        }
+    }
+
+    #[test]
+    fn count_overlaps_long_lines() {
+        let lines = [
+            Line::new((0, 0), (1_000_000_000, 0)),
+            Line::new((500_000_000, 0), (2_000_000_000, 0)),
+            Line::new((700_000_000, -5), (700_000_000, 5)),
+            Line::new((-3, -3), (3, 3)),
+        ];
+
+        assert_eq!(count_overlaps(&lines, true, Method::Sweep), 500_000_001 + 1);
+    }
+
+    #[test]
+    fn map_queries() {
+        let lines: Vec<Line> = [
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ]
+        .into_iter()
+        .map(Line::parse)
+        .collect();
+
+        for mut map in [Map::build(&lines), Map::sparse()] {
+            map.add_lines(&lines, true);
+
+            assert_eq!(map.count_at_least(1), 39);
+            assert_eq!(map.count_at_least(3), 2);
+            assert_eq!(
+                map.max_coverage(),
+                Some((3, vec![Coord::new(4, 4), Coord::new(6, 4)]))
+            );
+            assert_eq!(map.histogram(), BTreeMap::from([(1, 27), (2, 10), (3, 2)]));
+            assert_eq!(map.lines_at(&Coord::new(4, 4)), vec![1, 2, 8]);
+            assert_eq!(map.lines_at(&Coord::new(9, 9)), Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn map_lines_at_skipped_and_offset() {
+        let lines = [Line::new((0, 0), (4, 2)), Line::new((-1, 1), (4, 1))];
+        let mut map = Map::build(&lines);
+
+        map.add_lines(&lines, false);
+
+        assert_eq!(map.lines_at(&Coord::new(2, 1)), vec![1]);
+        assert_eq!(
+            map.max_coverage(),
+            Some((1, (-1..=4).map(|x| Coord::new(x, 1)).collect()))
+        );
+
+        let mut map = Map::new(5, 3).with_raster(Raster::Bresenham);
+        map.add_lines(&lines[..1], true);
+
+        assert_eq!(map.lines_at(&Coord::new(1, 1)), vec![0]);
+        assert_eq!(map.lines_at(&Coord::new(1, 0)), Vec::<usize>::new());
+    }
+
+    fn example_map() -> Map {
+        let lines: Vec<Line> = [
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ]
+        .into_iter()
+        .map(Line::parse)
+        .collect();
+        let mut map = Map::build(&lines);
+        map.add_lines(&lines, true);
+
+        map
+    }
+
+    #[test]
+    fn map_display() {
+        let diagram = [
+            "1.1....11.",
+            ".111...2..",
+            "..2.1.111.",
+            "...1.2.2..",
+            ".112313211",
+            "...1.2....",
+            "..1...1...",
+            ".1.....1..",
+            "1.......1.",
+            "222111....",
+        ];
+
+        assert_eq!(example_map().to_string(), diagram.join("\n") + "\n");
+
+        let mut map = Map::sparse();
+        map.add_lines(
+            &[Line::new((-2, 5), (0, 5)), Line::new((-1, 4), (-1, 6))],
+            false,
+        );
+
+        assert_eq!(map.to_string(), ".1.\n121\n.1.\n");
+        assert_eq!(Map::sparse().to_string(), "");
+    }
+
+    #[test]
+    fn export_images() {
+        let map = example_map();
+
+        let ppm = map.to_ppm(2).unwrap();
+        assert!(ppm.starts_with(b"P6\n20 20\n255\n"));
+        assert_eq!(ppm.len(), 13 + 20 * 20 * 3);
+        // (0, 0) is covered once and (4, 4) by the three busiest lines.
+        assert_eq!(ppm[13..16], [0x2c, 0x3e, 0x9c]);
+        assert_eq!(ppm[13 + (8 * 20 + 8) * 3..][..3], [0xd6, 0x2a, 0x1e]);
+        assert_eq!(ppm[13 + 2 * 3..][..3], [0, 0, 0]);
+
+        let png = map.to_png(2).unwrap();
+        assert_eq!(
+            png[..8],
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+        );
+        assert_eq!(png[12..16], *b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 20, 0, 0, 0, 20]);
+        assert_eq!(
+            png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+
+        let mut far = Map::sparse();
+        far.add_lines(
+            &[
+                Line::new((0, 0), (0, 0)),
+                Line::new((1 << 20, 1 << 20), (1 << 20, 1 << 20)),
+            ],
+            false,
+        );
+        let side = (1 << 20) + 1;
+        assert_eq!(
+            far.to_ppm(1),
+            Err(ExportError::TooLarge {
+                width: side,
+                height: side,
+                scale: 1
+            })
+        );
+        assert_eq!(
+            far.to_png(usize::MAX),
+            Err(ExportError::TooLarge {
+                width: side,
+                height: side,
+                scale: usize::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn export_svg() {
+        let svg = example_map().to_svg();
+
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">"#));
+        assert_eq!(svg.matches("<line ").count(), 10);
+        assert_eq!(svg.matches("<circle ").count(), 12);
+        assert!(svg.contains(r#"<line x1="0.5" y1="9.5" x2="5.5" y2="9.5"/>"#));
+        assert!(svg.contains(r#"<circle cx="4.5" cy="4.5" r="0.4"><title>3</title></circle>"#));
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = [
@@ -0,0 +1,254 @@
+use std::fmt::{self, Write};
+
+use super::{Coord, Map};
+
+/// The most pixels a raster export may have, so a map with far-apart lines
+/// can't ask for more memory than the machine has.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// Heatmap colours, from a single line up to the busiest point. Empty
+/// points are left black.
+const SCALE: [(u8, u8, u8); 4] = [
+    (0x2c, 0x3e, 0x9c),
+    (0x1f, 0xa1, 0x8a),
+    (0xf2, 0xd0, 0x2c),
+    (0xd6, 0x2a, 0x1e),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ExportError {
+    TooLarge {
+        width: usize,
+        height: usize,
+        scale: usize,
+    },
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLarge {
+                width,
+                height,
+                scale,
+            } => write!(
+                f,
+                "Map too large for raster export: {}x{} at scale {}!",
+                width, height, scale
+            ),
+        }
+    }
+}
+
+/// An RGB raster, one `scale` × `scale` block per map point.
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(&self.pixels);
+
+        bytes
+    }
+
+    /// A truecolour PNG whose image data is zlib-wrapped in stored
+    /// (uncompressed) deflate blocks, so no compressor is needed.
+    fn png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1) * 3).take(self.height) {
+            raw.push(0);
+            raw.extend(row);
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            zlib.push(blocks.peek().is_none() as u8);
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut bytes = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        for (kind, data) in [(b"IHDR", header), (b"IDAT", zlib), (b"IEND", vec![])] {
+            bytes.extend((data.len() as u32).to_be_bytes());
+            let start = bytes.len();
+            bytes.extend(kind);
+            bytes.extend(&data);
+            let crc = crc32(&bytes[start..]);
+            bytes.extend(crc.to_be_bytes());
+        }
+
+        bytes
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// Blends along `SCALE` for a count between 1 and `highest`.
+fn colour(count: usize, highest: usize) -> [u8; 3] {
+    if count == 0 {
+        return [0, 0, 0];
+    }
+
+    let position = match highest {
+        0 | 1 => 0.0,
+        _ => (count - 1) as f64 / (highest - 1) as f64 * (SCALE.len() - 1) as f64,
+    };
+    let low = (position.floor() as usize).min(SCALE.len() - 2);
+    let t = position - low as f64;
+    let (from, to) = (SCALE[low], SCALE[low + 1]);
+    let blend = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+    [
+        blend(from.0, to.0),
+        blend(from.1, to.1),
+        blend(from.2, to.2),
+    ]
+}
+
+fn centre(coordinate: i64) -> f64 {
+    coordinate as f64 + 0.5
+}
+
+impl Map {
+    fn heatmap(&self, scale: usize) -> Result<Image, ExportError> {
+        let scale = scale.max(1);
+        let too_large = ExportError::TooLarge {
+            width: self.width,
+            height: self.height,
+            scale,
+        };
+        let pixel_count = [self.height, scale, scale]
+            .iter()
+            .try_fold(self.width, |total, n| total.checked_mul(*n))
+            .filter(|&total| total <= MAX_PIXELS)
+            .ok_or(too_large)?;
+        let highest = self.max_coverage().map_or(0, |(highest, _)| highest);
+
+        let mut pixels = Vec::with_capacity(pixel_count * 3);
+        for y in 0..self.height as i64 {
+            let mut row = Vec::with_capacity(self.width * scale * 3);
+            for x in 0..self.width as i64 {
+                let count = self.count(&Coord::new(self.origin.x + x, self.origin.y + y));
+                for _ in 0..scale {
+                    row.extend(colour(count, highest));
+                }
+            }
+            for _ in 0..scale {
+                pixels.extend(&row);
+            }
+        }
+
+        Ok(Image {
+            width: self.width * scale,
+            height: self.height * scale,
+            pixels,
+        })
+    }
+
+    /// A binary PPM heatmap, `scale` pixels per point, coloured by how many
+    /// lines cover each point. Fails if the image would have more than
+    /// `MAX_PIXELS` pixels.
+    pub fn to_ppm(&self, scale: usize) -> Result<Vec<u8>, ExportError> {
+        Ok(self.heatmap(scale)?.ppm())
+    }
+
+    /// The same heatmap as `to_ppm`, encoded as a PNG.
+    pub fn to_png(&self, scale: usize) -> Result<Vec<u8>, ExportError> {
+        Ok(self.heatmap(scale)?.png())
+    }
+
+    /// An SVG with every drawn line as a segment through the centres of its
+    /// points, and every point covered more than once marked in red.
+    pub fn to_svg(&self) -> String {
+        let (low, width, height) = (&self.origin, self.width, self.height);
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            low.x, low.y, width, height
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#,
+            low.x, low.y, width, height
+        )
+        .unwrap();
+
+        svg.push_str(r#"<g stroke="steelblue" stroke-width="0.3" stroke-linecap="round">"#);
+        svg.push('\n');
+        for (_, line) in &self.lines {
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}"/>"#,
+                centre(line.start.x),
+                centre(line.start.y),
+                centre(line.end.x),
+                centre(line.end.y)
+            )
+            .unwrap();
+        }
+        svg.push_str("</g>\n");
+
+        let mut overlaps: Vec<(Coord, usize)> =
+            self.covered().filter(|(_, count)| *count > 1).collect();
+        overlaps.sort_by_key(|(point, _)| (point.y, point.x));
+
+        svg.push_str(r#"<g fill="red" fill-opacity="0.8">"#);
+        svg.push('\n');
+        for (point, count) in overlaps {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="0.4"><title>{}</title></circle>"#,
+                centre(point.x),
+                centre(point.y),
+                count
+            )
+            .unwrap();
+        }
+        svg.push_str("</g>\n</svg>\n");
+
+        svg
+    }
+}
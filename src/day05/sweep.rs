@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use super::{gcd, Line};
+
+/// The infinite line a segment lies on: primitive direction `(a, b)` with
+/// `a > 0` (or `a == 0, b > 0`) and `c = b * x - a * y`, which is the same
+/// for every point on it.
+type Carrier = (i64, i64, i64);
+
+/// A segment reduced to its carrier and the range of lattice steps `k` it
+/// covers, where a point's step is `(a * x + b * y)` divided by `a² + b²`.
+struct Segment {
+    carrier: Carrier,
+    steps: (i64, i64),
+    x_range: (i64, i64),
+    y_range: (i64, i64),
+}
+
+impl Segment {
+    fn new(line: &Line) -> Self {
+        let (start, end) = line.ends();
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let g = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+        // Single points get a horizontal carrier so they still group with
+        // the horizontal lines through them.
+        let (a, b) = if g == 0 { (1, 0) } else { (dx / g, dy / g) };
+        let (a, b) = if a < 0 || (a == 0 && b < 0) {
+            (-a, -b)
+        } else {
+            (a, b)
+        };
+
+        let step = |x: i64, y: i64| (a * x + b * y).div_euclid(a * a + b * b);
+        let (k1, k2) = (step(start.x, start.y), step(end.x, end.y));
+
+        Self {
+            carrier: (a, b, b * start.x - a * start.y),
+            steps: (k1.min(k2), k1.max(k2)),
+            x_range: (start.x.min(end.x), start.x.max(end.x)),
+            y_range: (start.y.min(end.y), start.y.max(end.y)),
+        }
+    }
+
+    fn contains(&self, (x, y): (i64, i64)) -> bool {
+        (self.x_range.0..=self.x_range.1).contains(&x)
+            && (self.y_range.0..=self.y_range.1).contains(&y)
+    }
+
+    /// The lattice point where two segments on different carriers cross.
+    fn crossing(&self, other: &Self) -> Option<(i64, i64)> {
+        let (a1, b1, c1) = self.carrier;
+        let (a2, b2, c2) = other.carrier;
+        let det = (a1 * b2 - a2 * b1) as i128;
+        if det == 0 {
+            return None;
+        }
+
+        let x = a1 as i128 * c2 as i128 - a2 as i128 * c1 as i128;
+        let y = b1 as i128 * c2 as i128 - b2 as i128 * c1 as i128;
+        if x % det != 0 || y % det != 0 {
+            return None;
+        }
+
+        let point = ((x / det) as i64, (y / det) as i64);
+        (self.contains(point) && other.contains(point)).then_some(point)
+    }
+}
+
+/// Stretches of steps covered by at least two segments on one carrier,
+/// merged and sorted.
+fn shared_steps(segments: &[&Segment]) -> Vec<(i64, i64)> {
+    let mut events: Vec<(i64, i32)> = segments
+        .iter()
+        .flat_map(|s| [(s.steps.0, 1), (s.steps.1 + 1, -1)])
+        .collect();
+    events.sort();
+
+    let mut shared: Vec<(i64, i64)> = vec![];
+    let mut depth = 0;
+    let mut opened = 0;
+    for (k, change) in events {
+        let was_shared = depth >= 2;
+        depth += change;
+
+        if !was_shared && depth >= 2 {
+            opened = k;
+        } else if was_shared && depth < 2 && k > opened {
+            match shared.last_mut() {
+                Some(last) if last.1 + 1 == opened => last.1 = k - 1,
+                _ => shared.push((opened, k - 1)),
+            }
+        }
+    }
+
+    shared
+}
+
+fn is_shared(shared: &[(i64, i64)], k: i64) -> bool {
+    let i = shared.partition_point(|&(_, end)| end < k);
+
+    shared.get(i).is_some_and(|&(start, _)| start <= k)
+}
+
+/// Counts lattice points covered by at least two lines without building a
+/// grid. Collinear overlaps are measured as step intervals on each carrier,
+/// crossings between carriers are found by sweeping segments in x order, and
+/// points counted both ways are only counted once.
+pub fn overlap_count(lines: &[&Line]) -> usize {
+    let segments: Vec<Segment> = lines.iter().map(|line| Segment::new(line)).collect();
+
+    let mut carriers: HashMap<Carrier, Vec<&Segment>> = HashMap::new();
+    for segment in &segments {
+        carriers.entry(segment.carrier).or_default().push(segment);
+    }
+    let shared: HashMap<Carrier, Vec<(i64, i64)>> = carriers
+        .iter()
+        .map(|(carrier, on_carrier)| (*carrier, shared_steps(on_carrier)))
+        .collect();
+
+    let mut by_x: Vec<&Segment> = segments.iter().collect();
+    by_x.sort_by_key(|s| s.x_range.0);
+    let mut active: Vec<&Segment> = vec![];
+    let mut crossings: HashMap<(i64, i64), Vec<Carrier>> = HashMap::new();
+    for segment in by_x {
+        active.retain(|other| other.x_range.1 >= segment.x_range.0);
+
+        for other in &active {
+            if let Some(point) = segment.crossing(other) {
+                let through = crossings.entry(point).or_default();
+                for carrier in [segment.carrier, other.carrier] {
+                    if !through.contains(&carrier) {
+                        through.push(carrier);
+                    }
+                }
+            }
+        }
+        active.push(segment);
+    }
+
+    let collinear: i64 = shared
+        .values()
+        .flatten()
+        .map(|(start, end)| end - start + 1)
+        .sum();
+    let adjustment: i64 = crossings
+        .iter()
+        .map(|(&(x, y), through)| {
+            let counted = through
+                .iter()
+                .filter(|&carrier| {
+                    let (a, b, _) = *carrier;
+                    is_shared(&shared[carrier], (a * x + b * y).div_euclid(a * a + b * b))
+                })
+                .count() as i64;
+
+            if counted == 0 {
+                1
+            } else {
+                1 - counted
+            }
+        })
+        .sum();
+
+    (collinear + adjustment) as usize
+}
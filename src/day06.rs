@@ -1,41 +1,514 @@
-const STARTING_AGE: usize = 8;
-const RESET_AGE: usize = 6;
+use std::fmt;
 
-pub fn solve<T: AsRef<str>>(input: &[T]) -> (usize, usize) {
+mod big;
+
+pub use big::BigUint;
+
+/// Life cycles whose transition matrix would need more states than this
+/// are rejected.
+const MAX_STATES: usize = 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum LifeCycleError {
+    NoOffspring,
+    ResetAboveNewborn {
+        reset_timer: usize,
+        newborn_timer: usize,
+    },
+    ZeroMaturityDelay,
+    ZeroDeathAge,
+    TooManyStates {
+        states: usize,
+    },
+    InvalidTimer {
+        timer: u8,
+    },
+    ZeroModulus,
+}
+
+impl fmt::Display for LifeCycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoOffspring => write!(f, "Litter size must be at least one!"),
+            Self::ResetAboveNewborn {
+                reset_timer,
+                newborn_timer,
+            } => write!(
+                f,
+                "Reset timer {} is longer than newborn timer {}!",
+                reset_timer, newborn_timer
+            ),
+            Self::ZeroMaturityDelay => write!(f, "Maturity delay must be positive if given!"),
+            Self::ZeroDeathAge => write!(f, "Death age must be positive if given!"),
+            Self::TooManyStates { states } => {
+                write!(
+                    f,
+                    "Life cycle needs {} states, more than {}!",
+                    states, MAX_STATES
+                )
+            }
+            Self::InvalidTimer { timer } => write!(f, "Fish timer {} is out of range!", timer),
+            Self::ZeroModulus => write!(f, "Modulus must be positive!"),
+        }
+    }
+}
+
+/// How a species of fish lives. Newborns spend `maturity_delay` days as
+/// juveniles, then start their timer at `newborn_timer`. A fish whose timer
+/// is at zero spawns `litter_size` newborns the next day and restarts at
+/// `reset_timer`. With a `death_age`, a fish is removed once it has lived
+/// that many days, after spawning that day if due; the starting fish count
+/// their age from the start of the simulation.
+///
+/// Every timer and juvenile day is tracked separately for each age below
+/// `death_age`, so `(newborn_timer + maturity_delay + 1) * death_age` can't
+/// be more than `MAX_STATES` (1024); mortal lanternfish can live at most 113
+/// days.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LifeCycle {
+    pub newborn_timer: usize,
+    pub reset_timer: usize,
+    pub maturity_delay: Option<usize>,
+    pub death_age: Option<usize>,
+    pub litter_size: u64,
+}
+
+impl LifeCycle {
+    /// Lanternfish, as in the puzzle.
+    pub const LANTERNFISH: Self = Self {
+        newborn_timer: 8,
+        reset_timer: 6,
+        maturity_delay: None,
+        death_age: None,
+        litter_size: 1,
+    };
+
+    pub fn validate(&self) -> Result<(), LifeCycleError> {
+        if self.litter_size == 0 {
+            return Err(LifeCycleError::NoOffspring);
+        }
+        if self.reset_timer > self.newborn_timer {
+            return Err(LifeCycleError::ResetAboveNewborn {
+                reset_timer: self.reset_timer,
+                newborn_timer: self.newborn_timer,
+            });
+        }
+        if self.maturity_delay == Some(0) {
+            return Err(LifeCycleError::ZeroMaturityDelay);
+        }
+        if self.death_age == Some(0) {
+            return Err(LifeCycleError::ZeroDeathAge);
+        }
+
+        let states = self.stages().saturating_mul(self.ages());
+        if states > MAX_STATES {
+            return Err(LifeCycleError::TooManyStates { states });
+        }
+
+        Ok(())
+    }
+
+    /// Timers `0..=newborn_timer`, followed by one stage per juvenile day.
+    fn stages(&self) -> usize {
+        self.newborn_timer
+            .saturating_add(self.maturity_delay.unwrap_or(0))
+            .saturating_add(1)
+    }
+
+    fn ages(&self) -> usize {
+        self.death_age.unwrap_or(1)
+    }
+
+    fn state(&self, stage: usize, age: usize) -> usize {
+        stage * self.ages() + age
+    }
+
+    /// Where each state's fish are the next day, as `(from, to, fish)`.
+    fn transitions(&self) -> Vec<(usize, usize, u64)> {
+        let newborn = self.stages() - 1;
+        let mut transitions = vec![];
+        for stage in 0..self.stages() {
+            for age in 0..self.ages() {
+                let from = self.state(stage, age);
+                if stage == 0 {
+                    transitions.push((from, self.state(newborn, 0), self.litter_size));
+                }
+
+                let next_age = match self.death_age {
+                    Some(death_age) if age + 1 == death_age => continue,
+                    Some(_) => age + 1,
+                    None => 0,
+                };
+                let next_stage = match stage {
+                    0 => self.reset_timer,
+                    _ => stage - 1,
+                };
+                transitions.push((from, self.state(next_stage, next_age), 1));
+            }
+        }
+
+        transitions
+    }
+
+    /// Counts per state for the starting fish, given by their timers.
+    fn initial<T: Count>(&self, fish: &[u8], zero: &T, one: &T) -> Result<Vec<T>, LifeCycleError> {
+        self.validate()?;
+
+        let mut counts = vec![zero.clone(); self.stages() * self.ages()];
+        for f in fish {
+            if *f as usize > self.newborn_timer {
+                return Err(LifeCycleError::InvalidTimer { timer: *f });
+            }
+            let state = self.state(*f as usize, 0);
+            counts[state] = counts[state].add(one);
+        }
+
+        Ok(counts)
+    }
+
+    /// The exact number of fish after `days`.
+    pub fn population(&self, fish: &[u8], days: u64) -> Result<BigUint, LifeCycleError> {
+        population(self, fish, days, BigUint::from(0), BigUint::from(1))
+    }
+
+    /// The number of fish after `days`, modulo `modulus`.
+    pub fn population_mod(
+        &self,
+        fish: &[u8],
+        days: u64,
+        modulus: u64,
+    ) -> Result<u64, LifeCycleError> {
+        if modulus == 0 {
+            return Err(LifeCycleError::ZeroModulus);
+        }
+
+        let zero = Modular { value: 0, modulus };
+        let one = Modular {
+            value: 1 % modulus,
+            modulus,
+        };
+
+        population(self, fish, days, zero, one).map(|count| count.value)
+    }
+
+    /// A census for every day, starting with the fish as given on day 0.
+    pub fn history(&self, fish: &[u8]) -> Result<History, LifeCycleError> {
+        let counts = self.initial(fish, &BigUint::from(0), &BigUint::from(1))?;
+
+        Ok(History {
+            life_cycle: self.clone(),
+            transitions: self.transitions(),
+            day: 0,
+            counts,
+        })
+    }
+
+    /// Column names for a census' stages: each timer, then each juvenile
+    /// stage by the days it has left before its timer starts.
+    pub fn stage_labels(&self) -> Vec<String> {
+        (0..self.stages())
+            .map(|stage| match stage.checked_sub(self.newborn_timer) {
+                Some(juvenile) if juvenile > 0 => format!("juvenile_{}", juvenile),
+                _ => format!("timer_{}", stage),
+            })
+            .collect()
+    }
+}
+
+/// How many fish are in each stage on one day. `stages` is indexed like
+/// `LifeCycle::stage_labels`, with fish of every age added together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Census {
+    pub day: u64,
+    pub stages: Vec<BigUint>,
+}
+
+impl Census {
+    pub fn total(&self) -> BigUint {
+        self.stages
+            .iter()
+            .fold(BigUint::from(0), |total, count| &total + count)
+    }
+}
+
+/// The endless day-by-day census of a school of fish.
+pub struct History {
+    life_cycle: LifeCycle,
+    transitions: Vec<(usize, usize, u64)>,
+    day: u64,
+    counts: Vec<BigUint>,
+}
+
+impl Iterator for History {
+    type Item = Census;
+
+    fn next(&mut self) -> Option<Census> {
+        let ages = self.life_cycle.ages();
+        let census = Census {
+            day: self.day,
+            stages: self
+                .counts
+                .chunks(ages)
+                .map(|stage| {
+                    stage
+                        .iter()
+                        .fold(BigUint::from(0), |total, count| &total + count)
+                })
+                .collect(),
+        };
+
+        let mut counts = vec![BigUint::from(0); self.counts.len()];
+        for (from, to, fish) in &self.transitions {
+            if self.counts[*from] != BigUint::from(0) {
+                let moved = &self.counts[*from] * &BigUint::from(*fish);
+                counts[*to] = &counts[*to] + &moved;
+            }
+        }
+        self.counts = counts;
+        self.day += 1;
+
+        Some(census)
+    }
+}
+
+/// The censuses as CSV: the day, the count in each stage, then the total.
+pub fn to_csv(labels: &[String], history: &[Census]) -> String {
+    let mut csv = format!("day,{},total\n", labels.join(","));
+    for census in history {
+        let stages: Vec<String> = census.stages.iter().map(BigUint::to_string).collect();
+        csv += &format!("{},{},{}\n", census.day, stages.join(","), census.total());
+    }
+
+    csv
+}
+
+/// One bar per day, `width` characters for the largest total, with bar
+/// lengths on a log scale so exponential growth shows as a straight line.
+pub fn growth_chart(history: &[Census], width: usize) -> String {
+    let totals: Vec<BigUint> = history.iter().map(Census::total).collect();
+    let scale = totals.iter().filter_map(BigUint::log10).fold(0.0, f64::max);
+    let day_width = history
+        .last()
+        .map_or(1, |census| census.day.to_string().len());
+
+    let mut chart = String::new();
+    for (census, total) in history.iter().zip(&totals) {
+        // Even a single fish gets a mark, so it's told apart from none.
+        let bar = match total.log10() {
+            None => 0,
+            Some(_) if scale == 0.0 => 1,
+            Some(log) => ((log / scale * width as f64).round() as usize).max(1),
+        };
+        chart += &format!(
+            "{:>day_width$} |{} {}\n",
+            census.day,
+            "#".repeat(bar),
+            total,
+            day_width = day_width
+        );
+    }
+
+    chart
+}
+
+impl Default for LifeCycle {
+    fn default() -> Self {
+        Self::LANTERNFISH
+    }
+}
+
+pub fn solve<T: AsRef<str>>(input: &[T]) -> (BigUint, BigUint) {
     let ages = parse_ages(input);
 
     (part_one(&ages), part_two(&ages))
 }
 
-fn part_one(ages: &Vec<u8>) -> usize {
+fn part_one(ages: &[u8]) -> BigUint {
     fish_after_days(ages, 80)
 }
 
-fn part_two(ages: &Vec<u8>) -> usize {
+fn part_two(ages: &[u8]) -> BigUint {
     fish_after_days(ages, 256)
 }
 
-fn fish_after_days(fish: &Vec<u8>, days: u16) -> usize {
-    let mut ages: Vec<usize> = vec![0; 9];
-    for f in fish {
-        ages[*f as usize] += 1;
+/// The arithmetic the transition matrix is multiplied in.
+trait Count: Clone {
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+    fn is_zero(&self) -> bool;
+}
+
+impl Count for BigUint {
+    fn add(&self, other: &Self) -> Self {
+        self + other
     }
 
-    for _ in 1..=days {
-        let newborns = ages[0];
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
 
-        for i in 0..STARTING_AGE {
-            ages[i] = ages[i + 1];
+    fn is_zero(&self) -> bool {
+        *self == BigUint::default()
+    }
+}
+
+/// A count reduced modulo `modulus`.
+#[derive(Clone)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Count for Modular {
+    fn add(&self, other: &Self) -> Self {
+        let value = (self.value as u128 + other.value as u128) % self.modulus as u128;
+
+        Self {
+            value: value as u64,
+            ..*self
         }
+    }
 
-        ages[RESET_AGE] += newborns;
-        ages[STARTING_AGE] = newborns;
+    fn mul(&self, other: &Self) -> Self {
+        let value = self.value as u128 * other.value as u128 % self.modulus as u128;
+
+        Self {
+            value: value as u64,
+            ..*self
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+}
+
+/// `n` as a count, by doubling `one`.
+fn times<T: Count>(n: u64, zero: &T, one: &T) -> T {
+    let mut total = zero.clone();
+    let mut power = one.clone();
+    let mut n = n;
+    while n > 0 {
+        if n & 1 == 1 {
+            total = total.add(&power);
+        }
+        power = power.add(&power);
+        n >>= 1;
     }
 
-    ages.iter().sum()
+    total
 }
 
-fn parse_ages<T: AsRef<str>>(input: &[T]) -> Vec<u8> {
+/// `[i][j]` is how many fish in state `i` one fish in state `j` becomes
+/// after some number of days.
+#[derive(Clone)]
+struct Matrix<T>(Vec<Vec<T>>);
+
+impl<T: Count> Matrix<T> {
+    /// The matrix for a single day of `life_cycle`.
+    fn day(life_cycle: &LifeCycle, zero: &T, one: &T) -> Self {
+        let states = life_cycle.stages() * life_cycle.ages();
+        let mut matrix = vec![vec![zero.clone(); states]; states];
+        for (from, to, fish) in life_cycle.transitions() {
+            matrix[to][from] = matrix[to][from].add(&times(fish, zero, one));
+        }
+
+        Self(matrix)
+    }
+
+    fn identity(states: usize, zero: &T, one: &T) -> Self {
+        let mut matrix = vec![vec![zero.clone(); states]; states];
+        for (state, row) in matrix.iter_mut().enumerate() {
+            row[state] = one.clone();
+        }
+
+        Self(matrix)
+    }
+
+    fn mul(&self, other: &Self, zero: &T) -> Self {
+        // Most states only lead to one or two others, so skipping zero
+        // entries saves most of the work.
+        let product = self
+            .0
+            .iter()
+            .map(|row| {
+                let mut sums = vec![zero.clone(); other.0.len()];
+                for (a, other_row) in row.iter().zip(&other.0) {
+                    if a.is_zero() {
+                        continue;
+                    }
+                    for (sum, b) in sums.iter_mut().zip(other_row) {
+                        if !b.is_zero() {
+                            *sum = sum.add(&a.mul(b));
+                        }
+                    }
+                }
+
+                sums
+            })
+            .collect();
+
+        Self(product)
+    }
+
+    /// Raises the one-day matrix to `days` by repeated squaring.
+    fn after_days(life_cycle: &LifeCycle, days: u64, zero: &T, one: &T) -> Self {
+        let mut power = Self::day(life_cycle, zero, one);
+        let mut result = Self::identity(power.0.len(), zero, one);
+        let mut days = days;
+        while days > 0 {
+            if days & 1 == 1 {
+                result = result.mul(&power, zero);
+            }
+            days >>= 1;
+            if days > 0 {
+                power = power.mul(&power, zero);
+            }
+        }
+
+        result
+    }
+}
+
+/// Total fish after `days`, using at most two matrix products per bit of
+/// `days`.
+fn population<T: Count>(
+    life_cycle: &LifeCycle,
+    fish: &[u8],
+    days: u64,
+    zero: T,
+    one: T,
+) -> Result<T, LifeCycleError> {
+    let counts = life_cycle.initial(fish, &zero, &one)?;
+    let matrix = Matrix::after_days(life_cycle, days, &zero, &one);
+
+    Ok(matrix
+        .0
+        .iter()
+        .flat_map(|row| row.iter().zip(&counts))
+        .fold(zero.clone(), |total, (descendants, count)| {
+            total.add(&descendants.mul(count))
+        }))
+}
+
+/// The exact number of lanternfish after `days`. The count grows by about
+/// 9% a day, so this is for days in the thousands or millions; beyond that
+/// use `fish_after_days_mod`.
+pub fn fish_after_days(fish: &[u8], days: u64) -> BigUint {
+    LifeCycle::LANTERNFISH
+        .population(fish, days)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// The number of lanternfish after `days`, modulo `modulus`, for any `u64`
+/// day.
+pub fn fish_after_days_mod(fish: &[u8], days: u64, modulus: u64) -> u64 {
+    LifeCycle::LANTERNFISH
+        .population_mod(fish, days, modulus)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn parse_ages<T: AsRef<str>>(input: &[T]) -> Vec<u8> {
     input
         .first()
         .unwrap()
@@ -49,12 +522,285 @@ fn parse_ages<T: AsRef<str>>(input: &[T]) -> Vec<u8> {
 mod tests {
     use super::*;
 
+    /// Counts lanternfish by timer, rotating the counts each day.
+    fn simulate(fish: &[u8], days: usize) -> u128 {
+        let mut timers = [0u128; 9];
+        for f in fish {
+            timers[*f as usize] += 1;
+        }
+        for _ in 0..days {
+            timers.rotate_left(1);
+            timers[6] += timers[8];
+        }
+
+        timers.iter().sum()
+    }
+
+    /// Follows every fish individually as `(juvenile days left, timer, age)`.
+    fn simulate_life_cycle(life_cycle: &LifeCycle, fish: &[u8], days: usize) -> usize {
+        let mut school: Vec<(usize, usize, usize)> =
+            fish.iter().map(|f| (0, *f as usize, 0)).collect();
+        for _ in 0..days {
+            let mut next = vec![];
+            for (juvenile, timer, age) in school {
+                let (juvenile, timer) = match (juvenile, timer) {
+                    (0, 0) => {
+                        let newborn = (
+                            life_cycle.maturity_delay.unwrap_or(0),
+                            life_cycle.newborn_timer,
+                            0,
+                        );
+                        next.extend((0..life_cycle.litter_size).map(|_| newborn));
+                        (0, life_cycle.reset_timer)
+                    }
+                    (0, timer) => (0, timer - 1),
+                    (juvenile, timer) => (juvenile - 1, timer),
+                };
+                if life_cycle.death_age != Some(age + 1) {
+                    next.push((juvenile, timer, age + 1));
+                }
+            }
+            school = next;
+        }
+
+        school.len()
+    }
+
+    #[test]
+    fn fish_after_days_matches_simulation() {
+        let fish = [3, 4, 3, 1, 2];
+
+        for days in [0, 1, 2, 18, 80, 100, 255, 256, 500] {
+            assert_eq!(
+                fish_after_days(&fish, days).to_string(),
+                simulate(&fish, days as usize).to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn life_cycle_matches_simulation() {
+        let fish = [3, 4, 3, 1, 2, 0];
+        let life_cycles = [
+            LifeCycle {
+                maturity_delay: Some(3),
+                ..LifeCycle::LANTERNFISH
+            },
+            LifeCycle {
+                death_age: Some(20),
+                ..LifeCycle::LANTERNFISH
+            },
+            LifeCycle {
+                litter_size: 3,
+                ..LifeCycle::LANTERNFISH
+            },
+            LifeCycle {
+                newborn_timer: 4,
+                reset_timer: 1,
+                maturity_delay: Some(2),
+                death_age: Some(9),
+                litter_size: 2,
+            },
+            LifeCycle {
+                newborn_timer: 0,
+                reset_timer: 0,
+                maturity_delay: None,
+                death_age: Some(1),
+                litter_size: 1,
+            },
+        ];
+
+        for life_cycle in &life_cycles {
+            let fish: Vec<u8> = fish
+                .iter()
+                .map(|f| *f.min(&(life_cycle.newborn_timer as u8)))
+                .collect();
+            for days in [0, 1, 5, 9, 10, 30, 41] {
+                assert_eq!(
+                    life_cycle.population(&fish, days).unwrap().to_u64(),
+                    Some(simulate_life_cycle(life_cycle, &fish, days as usize) as u64),
+                    "{:?} after {} days",
+                    life_cycle,
+                    days
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn life_cycle_errors() {
+        let invalid = [
+            (
+                LifeCycle {
+                    litter_size: 0,
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::NoOffspring,
+            ),
+            (
+                LifeCycle {
+                    reset_timer: 9,
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::ResetAboveNewborn {
+                    reset_timer: 9,
+                    newborn_timer: 8,
+                },
+            ),
+            (
+                LifeCycle {
+                    maturity_delay: Some(0),
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::ZeroMaturityDelay,
+            ),
+            (
+                LifeCycle {
+                    death_age: Some(0),
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::ZeroDeathAge,
+            ),
+            (
+                LifeCycle {
+                    death_age: Some(200),
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::TooManyStates { states: 1800 },
+            ),
+            (
+                LifeCycle {
+                    death_age: Some(114),
+                    ..LifeCycle::LANTERNFISH
+                },
+                LifeCycleError::TooManyStates { states: 1026 },
+            ),
+        ];
+
+        for (life_cycle, error) in invalid {
+            assert_eq!(life_cycle.validate(), Err(error));
+        }
+        assert_eq!(LifeCycle::default().validate(), Ok(()));
+        assert_eq!(
+            LifeCycle {
+                death_age: Some(113),
+                ..LifeCycle::LANTERNFISH
+            }
+            .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            LifeCycle::LANTERNFISH.population(&[3, 9], 1),
+            Err(LifeCycleError::InvalidTimer { timer: 9 })
+        );
+        assert_eq!(
+            LifeCycle::LANTERNFISH.population_mod(&[3], 1, 0),
+            Err(LifeCycleError::ZeroModulus)
+        );
+    }
+
+    #[test]
+    fn history_example() {
+        let fish = [3, 4, 3, 1, 2];
+        let history: Vec<Census> = LifeCycle::LANTERNFISH
+            .history(&fish)
+            .unwrap()
+            .take(81)
+            .collect();
+        let stages = |census: &Census| -> Vec<Option<u64>> {
+            census.stages.iter().map(BigUint::to_u64).collect()
+        };
+
+        assert_eq!(stages(&history[0]), [0, 1, 1, 2, 1, 0, 0, 0, 0].map(Some));
+        assert_eq!(stages(&history[2]), [1, 2, 1, 0, 0, 0, 1, 0, 1].map(Some));
+        assert_eq!(history[18].total(), BigUint::from(26));
+        assert_eq!(history[80].day, 80);
+        assert_eq!(history[80].total(), fish_after_days(&fish, 80));
+    }
+
+    #[test]
+    fn history_life_cycle() {
+        let life_cycle = LifeCycle {
+            newborn_timer: 4,
+            reset_timer: 1,
+            maturity_delay: Some(2),
+            death_age: Some(9),
+            litter_size: 2,
+        };
+        let fish = [0, 4, 2];
+
+        for (day, census) in life_cycle.history(&fish).unwrap().take(30).enumerate() {
+            assert_eq!(
+                census.total().to_u64(),
+                Some(simulate_life_cycle(&life_cycle, &fish, day) as u64)
+            );
+        }
+        assert_eq!(
+            life_cycle.stage_labels(),
+            [
+                "timer_0",
+                "timer_1",
+                "timer_2",
+                "timer_3",
+                "timer_4",
+                "juvenile_1",
+                "juvenile_2"
+            ]
+        );
+    }
+
+    #[test]
+    fn history_exports() {
+        let life_cycle = LifeCycle::LANTERNFISH;
+        let history: Vec<Census> = life_cycle.history(&[1]).unwrap().take(11).collect();
+
+        assert_eq!(
+            to_csv(&life_cycle.stage_labels(), &history[..3]),
+            "day,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8,total\n\
+             0,0,1,0,0,0,0,0,0,0,1\n\
+             1,1,0,0,0,0,0,0,0,0,1\n\
+             2,0,0,0,0,0,0,1,0,1,2\n"
+        );
+
+        let chart = growth_chart(&history, 10);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[0], " 0 |# 1");
+        assert_eq!(lines[2], " 2 |###### 2");
+        assert_eq!(lines[10], "10 |########## 3");
+        assert_eq!(growth_chart(&history[..2], 10), "0 |# 1\n1 |# 1\n");
+    }
+
+    #[test]
+    fn fish_after_days_big() {
+        let fish = [3, 4, 3, 1, 2];
+        let count = fish_after_days(&fish, 10_000);
+
+        assert_eq!(count.to_u64(), None);
+        assert_eq!(count.to_string().len(), 380);
+        assert!(count.to_string().starts_with("12295"));
+    }
+
+    #[test]
+    fn fish_after_days_modulus() {
+        let fish = [3, 4, 3, 1, 2];
+
+        assert_eq!(fish_after_days_mod(&fish, 256, 1_000_000_007), 984_457_357);
+        assert_eq!(fish_after_days_mod(&fish, 80, 1), 0);
+        assert_eq!(
+            fish_after_days_mod(&fish, 500, u64::MAX),
+            (simulate(&fish, 500) % u64::MAX as u128) as u64
+        );
+        // Only the modular count can reach this many days.
+        let far = fish_after_days_mod(&fish, u64::MAX, 1_000_000_007);
+        assert!(far < 1_000_000_007);
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = ["3,4,3,1,2"].into_iter().map(String::from).collect();
 
         let solution = solve(&input);
 
-        assert_eq!(solution, (5934, 26984457539));
+        assert_eq!(solution, (BigUint::from(5934), BigUint::from(26984457539)));
     }
 }
@@ -0,0 +1,161 @@
+use std::{
+    fmt,
+    ops::{Add, Mul},
+};
+
+/// An unsigned integer of any size, stored as base 2³² limbs with the least
+/// significant first and no trailing zero limbs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigUint(Vec<u32>);
+
+impl BigUint {
+    fn trim(mut self) -> Self {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+
+        self
+    }
+
+    /// Divides in place by a single limb, returning the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.0.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+
+        remainder as u32
+    }
+
+    /// The base 10 logarithm, close enough for drawing charts, or `None`
+    /// for zero.
+    pub fn log10(&self) -> Option<f64> {
+        let (&top, rest) = self.0.split_last()?;
+        let (mantissa, shift) = match rest.last() {
+            Some(&next) => (top as f64 * 2f64.powi(32) + next as f64, rest.len() - 1),
+            None => (top as f64, 0),
+        };
+
+        Some(mantissa.log10() + (shift * 32) as f64 * 2f64.log10())
+    }
+
+    /// The value as a `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.0[..] {
+            [] => Some(0),
+            [low] => Some(low as u64),
+            [low, high] => Some(((high as u64) << 32) | low as u64),
+            _ => None,
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self(vec![value as u32, (value >> 32) as u32]).trim()
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let mut sum = Vec::with_capacity(long.0.len() + 1);
+        let mut carry = 0u64;
+        for (i, limb) in long.0.iter().enumerate() {
+            let value = *limb as u64 + short.0.get(i).copied().unwrap_or(0) as u64 + carry;
+            sum.push(value as u32);
+            carry = value >> 32;
+        }
+        sum.push(carry as u32);
+
+        BigUint(sum).trim()
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.0.is_empty() || other.0.is_empty() {
+            return BigUint::default();
+        }
+
+        let mut product = vec![0u32; self.0.len() + other.0.len()];
+        for (i, a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.0.iter().enumerate() {
+                let value = *a as u64 * *b as u64 + product[i + j] as u64 + carry;
+                product[i + j] = value as u32;
+                carry = value >> 32;
+            }
+            product[i + other.0.len()] = carry as u32;
+        }
+
+        BigUint(product).trim()
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CHUNK: u32 = 1_000_000_000;
+
+        let mut rest = self.clone();
+        let mut chunks = vec![];
+        loop {
+            chunks.push(rest.div_rem_small(CHUNK));
+            if rest.0.is_empty() {
+                break;
+            }
+        }
+
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_uint_arithmetic() {
+        let a = BigUint::from(u64::MAX);
+        let b = BigUint::from(1);
+
+        assert_eq!((&a + &b).to_string(), "18446744073709551616");
+        assert_eq!(
+            (&a * &a).to_string(),
+            "340282366920938463426481119284349108225"
+        );
+        assert_eq!((&a * &BigUint::from(0)).to_string(), "0");
+        assert_eq!((&a + &b).to_u64(), None);
+        assert_eq!(BigUint::from(1_000_000_000).to_string(), "1000000000");
+        assert_eq!(BigUint::default().to_u64(), Some(0));
+    }
+
+    #[test]
+    fn big_uint_log10() {
+        let big = &BigUint::from(10_u64.pow(18)) * &BigUint::from(10_u64.pow(18));
+
+        assert_eq!(BigUint::default().log10(), None);
+        assert!((BigUint::from(1000).log10().unwrap() - 3.0).abs() < 1e-9);
+        assert!((big.log10().unwrap() - 36.0).abs() < 1e-9);
+    }
+}
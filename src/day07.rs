@@ -1,34 +1,349 @@
-use std::cmp::{max, min};
+use std::fmt;
 
 pub fn solve<T: AsRef<str>>(input: &[T]) -> (usize, usize) {
     let positions = parse_input(input);
 
-    (part_one(&positions), part_two(&positions))
+    (part_one(&positions).fuel, part_two(&positions).fuel)
 }
 
-fn part_one(positions: &Vec<usize>) -> usize {
-    let highest_position = positions.iter().max().unwrap();
+/// How much fuel a crab burns to move `distance` steps.
+pub trait FuelCost {
+    fn cost(&self, distance: usize) -> usize;
 
-    (0..=*highest_position)
-        .map(|p| positions.into_iter().map(|c| max(c, &p) - min(c, &p)).sum())
-        .min()
-        .unwrap()
+    /// Whether the cost never decreases and each step costs at least as
+    /// much as the one before, which makes the total fuel convex in the
+    /// meeting position. Models that can't promise this are searched
+    /// exhaustively.
+    fn is_convex(&self) -> bool {
+        false
+    }
+
+    /// Whether the cost is the distance times a constant, so charging a
+    /// move per axis costs the same as charging its Manhattan distance.
+    fn is_linear(&self) -> bool {
+        false
+    }
 }
 
-fn part_two(positions: &Vec<usize>) -> usize {
-    let highest_position = positions.iter().max().unwrap();
+/// One unit of fuel per step.
+pub struct Linear;
 
-    (0..=*highest_position)
-        .map(|p| {
-            positions
-                .into_iter()
-                .map(|c| {
-                    let n = max(c, &p) - min(c, &p);
-                    ((n * (n + 1)) / 2) as usize
-                })
-                .sum()
+/// One more unit for every step than for the step before.
+pub struct Triangular;
+
+/// The square of the distance.
+pub struct Quadratic;
+
+/// Costs given per distance, starting at zero steps. Beyond the table the
+/// last step's cost repeats.
+pub struct LookupTable(pub Vec<usize>);
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: usize) -> usize {
+        distance
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    fn is_linear(&self) -> bool {
+        true
+    }
+}
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: usize) -> usize {
+        distance * (distance + 1) / 2
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+impl FuelCost for Quadratic {
+    fn cost(&self, distance: usize) -> usize {
+        distance * distance
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+impl LookupTable {
+    fn step(&self, distance: usize) -> i128 {
+        match distance {
+            0 => 0,
+            _ => self.0[distance] as i128 - self.0[distance - 1] as i128,
+        }
+    }
+}
+
+impl FuelCost for LookupTable {
+    fn cost(&self, distance: usize) -> usize {
+        let Some(last) = self.0.len().checked_sub(1) else {
+            return 0;
+        };
+        if distance <= last {
+            return self.0[distance];
+        }
+
+        let extra = (distance - last) as i128 * self.step(last);
+        (self.0[last] as i128 + extra).clamp(0, usize::MAX as i128) as usize
+    }
+
+    fn is_convex(&self) -> bool {
+        (1..self.0.len()).all(|d| self.step(d) >= 0 && (d == 1 || self.step(d) >= self.step(d - 1)))
+    }
+}
+
+/// Where the crabs should meet and the fuel it takes them all to get there.
+/// The position is a `usize` on a line, or an array for a fleet in more
+/// dimensions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Alignment<P = usize> {
+    pub position: P,
+    pub fuel: usize,
+}
+
+pub fn total_fuel<C: FuelCost + ?Sized>(positions: &[usize], target: usize, cost: &C) -> usize {
+    positions
+        .iter()
+        .map(|c| cost.cost(c.abs_diff(target)))
+        .sum()
+}
+
+/// Finds the cheapest position between the outermost crabs, preferring the
+/// leftmost on ties. Convex costs are binary searched on the sign of the
+/// fuel difference between neighbouring positions; anything else tries
+/// every position.
+pub fn align<C: FuelCost + ?Sized>(positions: &[usize], cost: &C) -> Option<Alignment> {
+    let crabs: Vec<(usize, usize)> = positions.iter().map(|p| (*p, 1)).collect();
+
+    align_axis(&crabs, cost)
+}
+
+/// Fuel for crabs given as `(position, weight)` to meet at `target`.
+fn weighted_fuel<C: FuelCost + ?Sized>(crabs: &[(usize, usize)], target: usize, cost: &C) -> usize {
+    crabs
+        .iter()
+        .map(|(position, weight)| weight * cost.cost(position.abs_diff(target)))
+        .sum()
+}
+
+fn align_axis<C: FuelCost + ?Sized>(crabs: &[(usize, usize)], cost: &C) -> Option<Alignment> {
+    let lowest = crabs.iter().map(|(position, _)| *position).min()?;
+    let highest = crabs.iter().map(|(position, _)| *position).max()?;
+    let alignment = |position| Alignment {
+        position,
+        fuel: weighted_fuel(crabs, position, cost),
+    };
+
+    if !cost.is_convex() {
+        return (lowest..=highest)
+            .map(alignment)
+            .min_by_key(|alignment| (alignment.fuel, alignment.position));
+    }
+
+    let (mut low, mut high) = (lowest, highest);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if weighted_fuel(crabs, middle + 1, cost) < weighted_fuel(crabs, middle, cost) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    Some(alignment(low))
+}
+
+/// A crab in `D` dimensions that burns `weight` times the usual fuel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crab<const D: usize> {
+    pub position: [usize; D],
+    pub weight: usize,
+}
+
+/// How a move in several dimensions is charged: each axis separately, or
+/// once for the total Manhattan distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    PerAxis,
+    Manhattan,
+}
+
+impl<const D: usize> Crab<D> {
+    fn distance(&self, target: &[usize; D]) -> usize {
+        self.position
+            .iter()
+            .zip(target)
+            .map(|(a, b)| a.abs_diff(*b))
+            .sum()
+    }
+}
+
+pub fn fleet_fuel<const D: usize, C: FuelCost + ?Sized>(
+    crabs: &[Crab<D>],
+    target: &[usize; D],
+    cost: &C,
+    metric: Metric,
+) -> usize {
+    match metric {
+        Metric::PerAxis => crabs
+            .iter()
+            .map(|crab| {
+                let steps = crab.position.iter().zip(target);
+                crab.weight * steps.map(|(a, b)| cost.cost(a.abs_diff(*b))).sum::<usize>()
+            })
+            .sum(),
+        Metric::Manhattan => crabs
+            .iter()
+            .map(|crab| crab.weight * cost.cost(crab.distance(target)))
+            .sum(),
+    }
+}
+
+/// Finds the cheapest meeting point inside the box the crabs span. Per-axis
+/// costs, and Manhattan costs that are linear, split into one weighted line
+/// search per axis. Other convex Manhattan costs walk downhill from the
+/// per-axis answer, a step of at most one along every axis at a time, since
+/// the fuel is convex but doesn't split by axis. Anything else tries every
+/// point of the box, preferring the lowest coordinates on ties.
+pub fn align_fleet<const D: usize, C: FuelCost + ?Sized>(
+    crabs: &[Crab<D>],
+    cost: &C,
+    metric: Metric,
+) -> Option<Alignment<[usize; D]>> {
+    if crabs.is_empty() {
+        return None;
+    }
+
+    let per_axis = align_per_axis(crabs, cost)?;
+    if metric == Metric::PerAxis || cost.is_linear() {
+        return Some(per_axis);
+    }
+
+    let lowest: [usize; D] =
+        std::array::from_fn(|axis| crabs.iter().map(|c| c.position[axis]).min().unwrap());
+    let highest: [usize; D] =
+        std::array::from_fn(|axis| crabs.iter().map(|c| c.position[axis]).max().unwrap());
+
+    if cost.is_convex() {
+        let start = Alignment {
+            position: per_axis.position,
+            fuel: fleet_fuel(crabs, &per_axis.position, cost, metric),
+        };
+        return Some(descend(crabs, cost, &lowest, &highest, start));
+    }
+
+    let mut target = lowest;
+    let mut best: Option<Alignment<[usize; D]>> = None;
+    loop {
+        let fuel = fleet_fuel(crabs, &target, cost, metric);
+        if best.is_none_or(|best| fuel < best.fuel) {
+            best = Some(Alignment {
+                position: target,
+                fuel,
+            });
+        }
+
+        // Step to the next point like an odometer, last axis fastest.
+        let Some(axis) = (0..D).rev().find(|axis| target[*axis] < highest[*axis]) else {
+            return best;
+        };
+        target[axis] += 1;
+        target[axis + 1..].copy_from_slice(&lowest[axis + 1..]);
+    }
+}
+
+fn align_per_axis<const D: usize, C: FuelCost + ?Sized>(
+    crabs: &[Crab<D>],
+    cost: &C,
+) -> Option<Alignment<[usize; D]>> {
+    let mut position = [0; D];
+    let mut fuel = 0;
+    for (axis, coordinate) in position.iter_mut().enumerate() {
+        let line: Vec<(usize, usize)> = crabs
+            .iter()
+            .map(|crab| (crab.position[axis], crab.weight))
+            .collect();
+        let alignment = align_axis(&line, cost)?;
+        *coordinate = alignment.position;
+        fuel += alignment.fuel;
+    }
+
+    Some(Alignment { position, fuel })
+}
+
+/// Moves to the cheapest of the up to `3^D - 1` neighbouring points inside
+/// the box, diagonals included, until none is cheaper. Steps along a single
+/// axis aren't enough: a convex Manhattan fuel can be cheaper only along a
+/// diagonal.
+fn descend<const D: usize, C: FuelCost + ?Sized>(
+    crabs: &[Crab<D>],
+    cost: &C,
+    lowest: &[usize; D],
+    highest: &[usize; D],
+    start: Alignment<[usize; D]>,
+) -> Alignment<[usize; D]> {
+    let mut best = start;
+    loop {
+        let mut next = best;
+        for offsets in 0..3usize.pow(D as u32) {
+            let mut target = best.position;
+            let mut rest = offsets;
+            for (axis, coordinate) in target.iter_mut().enumerate() {
+                *coordinate = match rest % 3 {
+                    0 => coordinate.saturating_sub(1).max(lowest[axis]),
+                    1 => *coordinate,
+                    _ => (*coordinate + 1).min(highest[axis]),
+                };
+                rest /= 3;
+            }
+
+            let fuel = fleet_fuel(crabs, &target, cost, Metric::Manhattan);
+            if fuel < next.fuel {
+                next = Alignment {
+                    position: target,
+                    fuel,
+                };
+            }
+        }
+
+        if next.fuel == best.fuel {
+            return best;
+        }
+        best = next;
+    }
+}
+
+/// Any median minimises the sum of distances.
+fn part_one(positions: &[usize]) -> Alignment {
+    let mut sorted = positions.to_vec();
+    let middle = sorted.len() / 2;
+    let (_, median, _) = sorted.select_nth_unstable(middle);
+
+    Alignment {
+        position: *median,
+        fuel: total_fuel(positions, *median, &Linear),
+    }
+}
+
+/// The triangular cost is `(d² + |d|) / 2`, so its minimum is within half a
+/// step of the mean and one of the two integers around it is optimal.
+fn part_two(positions: &[usize]) -> Alignment {
+    let mean = positions.iter().sum::<usize>() / positions.len();
+
+    [mean, mean + 1]
+        .into_iter()
+        .map(|position| Alignment {
+            position,
+            fuel: total_fuel(positions, position, &Triangular),
         })
-        .min()
+        .min_by_key(|alignment| alignment.fuel)
         .unwrap()
 }
 
@@ -42,10 +357,311 @@ fn parse_input<T: AsRef<str>>(input: &[T]) -> Vec<usize> {
         .collect()
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    InvalidCrab { line: usize, value: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidCrab { line, value } => {
+                write!(f, "Not a crab on line {}: {:?}!", line + 1, value)
+            }
+        }
+    }
+}
+
+/// Reads one crab per line as its comma-separated coordinates, optionally
+/// followed by whitespace and a weight, which otherwise defaults to one.
+pub fn parse_fleet<const D: usize, T: AsRef<str>>(input: &[T]) -> Result<Vec<Crab<D>>, ParseError> {
+    input
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().trim().is_empty())
+        .map(|(line, value)| {
+            let value = value.as_ref().trim();
+            let invalid = || ParseError::InvalidCrab {
+                line,
+                value: value.to_string(),
+            };
+            let (position, weight) = match value.split_once(char::is_whitespace) {
+                Some((position, weight)) => {
+                    (position, weight.trim().parse().map_err(|_| invalid())?)
+                }
+                None => (value, 1),
+            };
+            let coordinates = position
+                .split(',')
+                .map(|s| s.trim().parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| invalid())?;
+
+            Ok(Crab {
+                position: coordinates.try_into().map_err(|_| invalid())?,
+                weight,
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Tries every position from 0 to the furthest crab.
+    fn brute_force<C: FuelCost + ?Sized>(positions: &[usize], cost: &C) -> usize {
+        let highest_position = positions.iter().max().unwrap();
+
+        (0..=*highest_position)
+            .map(|p| total_fuel(positions, p, cost))
+            .min()
+            .unwrap()
+    }
+
+    fn random_fleets() -> Vec<Vec<usize>> {
+        let mut seed: u64 = 7;
+        let mut next = |range: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % range) as usize
+        };
+
+        (0..200)
+            .map(|_| {
+                let len = 1 + next(12);
+                let range = 1 + next(60) as u64;
+                (0..len).map(|_| next(range)).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn closed_forms_match_brute_force() {
+        for positions in random_fleets() {
+            assert_eq!(part_one(&positions).fuel, brute_force(&positions, &Linear));
+            assert_eq!(
+                part_two(&positions).fuel,
+                brute_force(&positions, &Triangular)
+            );
+        }
+    }
+
+    #[test]
+    fn align_matches_brute_force() {
+        let costs: [&dyn FuelCost; 6] = [
+            &Linear,
+            &Triangular,
+            &Quadratic,
+            &LookupTable(vec![0, 1, 3, 10, 30]),
+            &LookupTable(vec![0, 5, 6, 7, 20, 21]),
+            &LookupTable(vec![]),
+        ];
+
+        for positions in random_fleets() {
+            for cost in costs {
+                let alignment = align(&positions, cost).unwrap();
+
+                assert_eq!(alignment.fuel, brute_force(&positions, cost));
+                assert_eq!(
+                    alignment.fuel,
+                    total_fuel(&positions, alignment.position, cost)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_table() {
+        let convex = LookupTable(vec![0, 1, 3, 6]);
+        let bumpy = LookupTable(vec![0, 5, 6, 7]);
+
+        assert!(convex.is_convex());
+        assert!(!bumpy.is_convex());
+        assert!(!LookupTable(vec![4, 2]).is_convex());
+        assert_eq!(convex.cost(5), 12);
+        assert_eq!(LookupTable(vec![4, 2]).cost(9), 0);
+        assert_eq!(LookupTable(vec![]).cost(9), 0);
+    }
+
+    #[test]
+    fn align_positions() {
+        let positions = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+        assert_eq!(
+            align(&positions, &Linear),
+            Some(Alignment {
+                position: 2,
+                fuel: 37
+            })
+        );
+        assert_eq!(
+            align(&positions, &Triangular),
+            Some(Alignment {
+                position: 5,
+                fuel: 168
+            })
+        );
+        assert_eq!(part_two(&positions).position, 5);
+        assert_eq!(align(&[], &Linear), None);
+    }
+
+    #[test]
+    fn weighted_crabs() {
+        let crabs = [(0, 5), (10, 1), (4, 0), (7, 2)];
+        let positions: Vec<usize> = crabs
+            .iter()
+            .flat_map(|(position, weight)| vec![*position; *weight])
+            .collect();
+
+        for cost in [
+            &Linear as &dyn FuelCost,
+            &Triangular,
+            &LookupTable(vec![0, 4, 5, 9]),
+        ] {
+            let fleet: Vec<Crab<1>> = crabs
+                .iter()
+                .map(|(position, weight)| Crab {
+                    position: [*position],
+                    weight: *weight,
+                })
+                .collect();
+
+            for metric in [Metric::PerAxis, Metric::Manhattan] {
+                let alignment = align_fleet(&fleet, cost, metric).unwrap();
+                assert_eq!(alignment.fuel, brute_force(&positions, cost));
+            }
+        }
+    }
+
+    #[test]
+    fn fleet_matches_brute_force() {
+        let fleets = random_fleets();
+        let costs: [&dyn FuelCost; 5] = [
+            &Linear,
+            &Triangular,
+            &Quadratic,
+            &LookupTable(vec![0, 0, 1, 2, 4]),
+            &LookupTable(vec![0, 5, 6, 7, 20, 21]),
+        ];
+
+        for chunk in fleets.chunks(3).take(40) {
+            let crabs: Vec<Crab<3>> = (0..chunk[0].len())
+                .map(|i| Crab {
+                    position: [
+                        chunk[0][i] % 12,
+                        chunk[1].get(i).copied().unwrap_or(3) % 12,
+                        chunk[2].get(i).copied().unwrap_or(5) % 12,
+                    ],
+                    weight: 1 + i % 3,
+                })
+                .collect();
+
+            for cost in costs {
+                for metric in [Metric::PerAxis, Metric::Manhattan] {
+                    let mut best = usize::MAX;
+                    for x in 0..12 {
+                        for y in 0..12 {
+                            for z in 0..12 {
+                                best = best.min(fleet_fuel(&crabs, &[x, y, z], cost, metric));
+                            }
+                        }
+                    }
+                    let alignment = align_fleet(&crabs, cost, metric).unwrap();
+
+                    assert_eq!(alignment.fuel, best);
+                    assert_eq!(fleet_fuel(&crabs, &alignment.position, cost, metric), best);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fleet_in_two_dimensions() {
+        let crabs: Vec<Crab<2>> = parse_fleet(&["0,0", "4,0 3", "", "0,6"]).unwrap();
+
+        assert_eq!(
+            crabs[1],
+            Crab {
+                position: [4, 0],
+                weight: 3
+            }
+        );
+        assert_eq!(
+            align_fleet(&crabs, &Linear, Metric::Manhattan),
+            Some(Alignment {
+                position: [4, 0],
+                fuel: 14
+            })
+        );
+        assert_eq!(
+            align_fleet(&crabs, &Triangular, Metric::PerAxis),
+            Some(Alignment {
+                position: [2, 1],
+                fuel: 34
+            })
+        );
+        assert_eq!(
+            align_fleet(&crabs, &Triangular, Metric::Manhattan),
+            Some(Alignment {
+                position: [1, 0],
+                fuel: 47
+            })
+        );
+        assert_eq!(align_fleet::<2, _>(&[], &Linear, Metric::PerAxis), None);
+    }
+
+    #[test]
+    fn fleet_far_apart() {
+        let crabs = [
+            Crab {
+                position: [0, 0, 0],
+                weight: 1,
+            },
+            Crab {
+                position: [1_000_000, 2_000_000, 0],
+                weight: 1,
+            },
+        ];
+
+        let linear = align_fleet(&crabs, &Linear, Metric::Manhattan).unwrap();
+        assert_eq!(linear.fuel, 3_000_000);
+        let triangular = align_fleet(&crabs, &Triangular, Metric::Manhattan).unwrap();
+        assert_eq!(triangular.fuel, 2_250_001_500_000);
+        assert_eq!(
+            fleet_fuel(&crabs, &triangular.position, &Triangular, Metric::Manhattan),
+            triangular.fuel
+        );
+    }
+
+    #[test]
+    fn fleet_parse_errors() {
+        assert_eq!(
+            parse_fleet::<2, _>(&["1,2", "1,2,3"]),
+            Err(ParseError::InvalidCrab {
+                line: 1,
+                value: String::from("1,2,3")
+            })
+        );
+        assert_eq!(
+            parse_fleet::<1, _>(&["4 heavy"]),
+            Err(ParseError::InvalidCrab {
+                line: 0,
+                value: String::from("4 heavy")
+            })
+        );
+    }
+
+    #[test]
+    fn large_positions() {
+        let positions = [0, 3_000_000, 1_000_000, 2_000_000, 7];
+
+        assert_eq!(part_one(&positions).fuel, 4_999_993);
+        assert_eq!(part_two(&positions).fuel, 3_399_994_200_017);
+        assert_eq!(align(&positions, &Triangular), Some(part_two(&positions)));
+    }
+
     #[test]
     fn example_case() {
         let input: Vec<String> = ["16,1,2,0,4,2,7,1,2,14"]
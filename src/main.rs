@@ -1,14 +1,6 @@
-use std::{env, fs, process};
-
-mod day01;
-mod day02;
-mod day03;
-mod day04;
-mod day05;
-mod day06;
-mod day07;
-mod day08;
-mod day09;
+use std::{env, fmt::Display, fs, net::TcpListener, process, time::Duration};
+
+use advent_of_code::{day01, day02, day03, day04, day05, day06, day07, day08, day09};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -19,14 +11,175 @@ fn main() {
     let day = &args[1];
     let input = read_input(day);
 
+    if let Some(mode) = args.get(2) {
+        run_mode(day, mode, &args[3..], &input);
+        return;
+    }
+
     println!("Calculating solution for day: {}", day);
 
-    let (part_one, part_two) = solve_fn(day)(&input);
+    match day.as_str() {
+        "1" => print_solution(day01::solve(&input)),
+        "2" => print_solution(day02::solve(&input)),
+        "3" => print_solution(day03::solve(&input)),
+        "4" => print_solution(day04::solve(&input)),
+        "5" => print_solution(day05::solve(&input)),
+        "6" => print_solution(day06::solve(&input)),
+        "7" => print_solution(day07::solve(&input)),
+        "8" => print_solution(day08::solve(&input)),
+        "9" => print_solution(day09::solve(&input)),
+        _ => {
+            println!("No implementation found for day {}!", day);
+            process::exit(1);
+        }
+    }
+}
 
+fn print_solution<A: Display, B: Display>((part_one, part_two): (A, B)) {
     println!("The solution to Part One is: {}", part_one);
     println!("The solution to Part Two is: {}", part_two);
 }
 
+fn run_mode(day: &str, mode: &str, options: &[String], input: &[String]) {
+    match (day, mode) {
+        ("4", "analyze") => {
+            let trials = parse_option(options, 0, 10_000);
+            let seed = parse_option(options, 1, 0);
+            if trials == 0 {
+                println!("At least one trial is needed!");
+                process::exit(1);
+            }
+            let game = day04::Game::build(input).unwrap_or_else(|e| {
+                println!("Could not build game because: {}", e);
+                process::exit(1);
+            });
+
+            println!("Simulating {} draw orders with seed {}", trials, seed);
+            for odds in game.analyze(trials, seed) {
+                println!("{}", odds);
+            }
+        }
+        ("4", "serve") => {
+            let port: u16 = parse_option(options, 0, 7878);
+            let interval = Duration::from_millis(parse_option(options, 1, 2000));
+            let game = day04::Game::build(input).unwrap_or_else(|e| {
+                println!("Could not build game because: {}", e);
+                process::exit(1);
+            });
+            let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+                println!("Could not listen on port {} because: {}", port, e);
+                process::exit(1);
+            });
+
+            println!("Serving bingo on 127.0.0.1:{}", port);
+            day04::serve(game, listener, interval).unwrap_or_else(|e| {
+                println!("Server failed because: {}", e);
+                process::exit(1);
+            });
+        }
+        ("4", "rig") => {
+            let card: usize = parse_option(options, 0, 1);
+            let goal = match options.get(1).map(String::as_str) {
+                Some("last") => day04::Goal::Last,
+                _ => day04::Goal::First,
+            };
+            let game = day04::Game::build(input).unwrap_or_else(|e| {
+                println!("Could not build game because: {}", e);
+                process::exit(1);
+            });
+
+            match card.checked_sub(1).and_then(|index| game.rig(index, goal)) {
+                Some(order) => {
+                    let order: Vec<String> = order.iter().map(|n| n.to_string()).collect();
+                    println!("{}", order.join(","));
+                }
+                None => println!("Card {} can't be made to win {:?}!", card, goal),
+            }
+        }
+        ("5", "export") => {
+            let format = options.first().map_or("text", String::as_str);
+            let path = match options.get(1) {
+                Some(path) => path.clone(),
+                None => format!("day05.{}", if format == "text" { "txt" } else { format }),
+            };
+            let scale = parse_option(options, 2, 1);
+            let lines: Vec<day05::Line> = input.iter().map(|s| day05::Line::parse(s)).collect();
+            let mut map = day05::Map::build(&lines);
+            map.add_lines(&lines, true);
+
+            let contents = match format {
+                "text" => map.to_string().into_bytes(),
+                "ppm" | "png" => {
+                    let image = if format == "ppm" {
+                        map.to_ppm(scale)
+                    } else {
+                        map.to_png(scale)
+                    };
+                    image.unwrap_or_else(|e| {
+                        println!("Could not export because: {}", e);
+                        process::exit(1);
+                    })
+                }
+                "svg" => map.to_svg().into_bytes(),
+                _ => {
+                    println!("Unknown export format: {}", format);
+                    process::exit(1);
+                }
+            };
+            fs::write(&path, contents).unwrap_or_else(|e| {
+                println!("Could not write {} because: {}", path, e);
+                process::exit(1);
+            });
+            println!("Exported the vent map to {}", path);
+        }
+        ("6", "history") => {
+            let days: usize = parse_option(options, 0, 80);
+            let life_cycle = day06::LifeCycle::LANTERNFISH;
+            let history: Vec<day06::Census> = life_cycle
+                .history(&day06::parse_ages(input))
+                .unwrap_or_else(|e| {
+                    println!("Could not simulate because: {}", e);
+                    process::exit(1);
+                })
+                .take(days + 1)
+                .collect();
+
+            let contents = match options.get(1).map_or("csv", String::as_str) {
+                "csv" => day06::to_csv(&life_cycle.stage_labels(), &history),
+                "chart" => day06::growth_chart(&history, 60),
+                format => {
+                    println!("Unknown history format: {}", format);
+                    process::exit(1);
+                }
+            };
+            match options.get(2) {
+                Some(path) => {
+                    fs::write(path, contents).unwrap_or_else(|e| {
+                        println!("Could not write {} because: {}", path, e);
+                        process::exit(1);
+                    });
+                    println!("Exported {} days of history to {}", days, path);
+                }
+                None => print!("{}", contents),
+            }
+        }
+        _ => {
+            println!("No {} mode found for day {}!", mode, day);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_option<T: std::str::FromStr>(options: &[String], index: usize, default: T) -> T {
+    match options.get(index) {
+        Some(option) => option.parse().unwrap_or_else(|_| {
+            println!("Invalid option: {}", option);
+            process::exit(1);
+        }),
+        None => default,
+    }
+}
+
 fn read_input(day: &String) -> Vec<String> {
     let filename = format!("inputs/day{:0>2}", day).to_string();
     println!("Reading input from file: {}", filename);
@@ -39,21 +192,3 @@ fn read_input(day: &String) -> Vec<String> {
         }
     }
 }
-
-fn solve_fn<T: AsRef<str>>(day: &String) -> fn(&[T]) -> (usize, usize) {
-    match day.as_str() {
-        "1" => day01::solve,
-        "2" => day02::solve,
-        "3" => day03::solve,
-        "4" => day04::solve,
-        "5" => day05::solve,
-        "6" => day06::solve,
-        "7" => day07::solve,
-        "8" => day08::solve,
-        "9" => day09::solve,
-        _ => {
-            println!("No implementation found for day {}!", day);
-            process::exit(1);
-        }
-    }
-}